@@ -0,0 +1,38 @@
+//! Compares the cost of pruning `Vec<f64>` values under `DefaultCommonality` and
+//! `EmptyCommonality`.
+//!
+//! Run with `cargo bench --bench commonality`.
+
+use std::{hint::black_box, time::Instant};
+
+use total_maps::{Commonality, DefaultCommonality, EmptyCommonality, TotalHashMap};
+
+const KEYS: u64 = 100_000;
+const ROUNDS: u64 = 10;
+
+fn main() {
+    bench::<DefaultCommonality>("DefaultCommonality");
+    bench::<EmptyCommonality>("EmptyCommonality");
+}
+
+fn bench<C: Commonality<Vec<f64>>>(name: &str) {
+    let mut map = TotalHashMap::<u64, Vec<f64>, C>::new();
+    map.reserve(KEYS as usize);
+    let mut run = || {
+        // Each key is populated and then pruned by resetting it to the empty vector
+        for key in 0..KEYS {
+            map.insert(key, vec![key as f64; 4]);
+        }
+        for key in 0..KEYS {
+            map.entry(black_box(key)).clear();
+        }
+        assert!(map.is_empty());
+    };
+    run();
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        run();
+    }
+    let elapsed = start.elapsed();
+    println!("{name:<24}{:>8.1} ns/key", elapsed.as_nanos() as f64 / (KEYS * ROUNDS) as f64);
+}
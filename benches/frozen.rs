@@ -0,0 +1,32 @@
+//! Compares lookup latency of `FrozenTotalMap` against `TotalBTreeMap`.
+//!
+//! Run with `cargo bench --bench frozen`.
+
+use std::{hint::black_box, time::Instant};
+
+use total_maps::TotalBTreeMap;
+
+const LEN: u64 = 100_000;
+const LOOKUPS: u64 = 1_000_000;
+
+fn main() {
+    // Store every other key, so that half of the lookups hit the common value
+    let map = (0..LEN).map(|key| (key * 2, key + 1)).collect::<TotalBTreeMap<_, _>>();
+    let frozen = map.clone().freeze();
+
+    bench("TotalBTreeMap::get", || lookup_all(|key| *map.get(&key)));
+    bench("FrozenTotalMap::get", || lookup_all(|key| *frozen.get(&key)));
+}
+
+fn lookup_all(get: impl Fn(u64) -> u64) -> u64 {
+    // A multiplicative hash scatters the keys, so that lookups don't benefit from locality
+    (0..LOOKUPS).map(|i| get(black_box(i.wrapping_mul(0x9e37_79b9) % (LEN * 2)))).sum()
+}
+
+fn bench(name: &str, f: impl Fn() -> u64) {
+    black_box(f());
+    let start = Instant::now();
+    black_box(f());
+    let elapsed = start.elapsed();
+    println!("{name:<24}{:>8.1} ns/lookup", elapsed.as_nanos() as f64 / LOOKUPS as f64);
+}
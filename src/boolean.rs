@@ -0,0 +1,56 @@
+//! Provides [FalseCommonality] and [TrueCommonality], [commonalities](Commonality) for [bool].
+
+use crate::{Commonality, ConstCommonality};
+
+/// A [commonality](Commonality) for [bool], under which `false` is the *common* value.
+///
+/// A [TotalHashMap](crate::TotalHashMap) or [TotalBTreeMap](crate::TotalBTreeMap) using this
+/// commonality only stores the keys associated with `true`, so it behaves like a set of those keys:
+///
+/// ```
+/// # use total_maps::{FalseCommonality, TotalHashMap};
+/// let mut set = TotalHashMap::<&str, bool, FalseCommonality>::new();
+/// set.insert("foo", true);
+/// set.insert("bar", true);
+/// set.insert("bar", false);
+/// assert!(set["foo"]);
+/// assert!(!set["bar"]);
+/// assert_eq!(set.keys().collect::<Vec<_>>(), [&"foo"]);
+/// ```
+pub struct FalseCommonality(());
+
+impl Commonality<bool> for FalseCommonality {
+    const CHEAP: bool = true;
+
+    fn common() -> bool {
+        false
+    }
+    fn is_common(value: &bool) -> bool {
+        !*value
+    }
+}
+
+impl ConstCommonality<bool> for FalseCommonality {
+    const COMMON: bool = false;
+}
+
+/// A [commonality](Commonality) for [bool], under which `true` is the *common* value.
+///
+/// A [TotalHashMap](crate::TotalHashMap) or [TotalBTreeMap](crate::TotalBTreeMap) using this
+/// commonality only stores the keys associated with `false`.
+pub struct TrueCommonality(());
+
+impl Commonality<bool> for TrueCommonality {
+    const CHEAP: bool = true;
+
+    fn common() -> bool {
+        true
+    }
+    fn is_common(value: &bool) -> bool {
+        *value
+    }
+}
+
+impl ConstCommonality<bool> for TrueCommonality {
+    const COMMON: bool = true;
+}
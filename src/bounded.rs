@@ -0,0 +1,72 @@
+//! Maps whose *common* value is the minimum or maximum value of a [Bounded] type.
+//!
+//! These are useful for maps that track a running maximum (or minimum) per key: only keys that
+//! have seen a value above the minimum (or below the maximum) are stored.
+
+use num_traits::Bounded;
+
+use crate::{Commonality, ConstCommonality, TotalBTreeMap, TotalHashMap};
+
+/// A hash map that only stores entries with values above the minimum. All other keys are presumed
+/// to be associated with the minimum value.
+pub type AboveMinHashMap<K, V> = TotalHashMap<K, V, MinCommonality>;
+
+/// An ordered map that only stores entries with values above the minimum. All other keys are
+/// presumed to be associated with the minimum value.
+pub type AboveMinBTreeMap<K, V> = TotalBTreeMap<K, V, MinCommonality>;
+
+/// A hash map that only stores entries with values below the maximum. All other keys are presumed
+/// to be associated with the maximum value.
+pub type BelowMaxHashMap<K, V> = TotalHashMap<K, V, MaxCommonality>;
+
+/// An ordered map that only stores entries with values below the maximum. All other keys are
+/// presumed to be associated with the maximum value.
+pub type BelowMaxBTreeMap<K, V> = TotalBTreeMap<K, V, MaxCommonality>;
+
+/// A [commonality](Commonality) under which the minimum value of a [Bounded] type is the *common*
+/// value.
+///
+/// A [TotalHashMap] or [TotalBTreeMap] using this commonality only stores entries with values
+/// above the minimum.
+pub struct MinCommonality(());
+
+impl<T: Bounded + PartialEq> Commonality<T> for MinCommonality {
+    const CHEAP: bool = true;
+
+    fn common() -> T {
+        T::min_value()
+    }
+    fn is_common(value: &T) -> bool {
+        *value == T::min_value()
+    }
+}
+
+/// A [commonality](Commonality) under which the maximum value of a [Bounded] type is the *common*
+/// value.
+///
+/// A [TotalHashMap] or [TotalBTreeMap] using this commonality only stores entries with values
+/// below the maximum.
+pub struct MaxCommonality(());
+
+impl<T: Bounded + PartialEq> Commonality<T> for MaxCommonality {
+    const CHEAP: bool = true;
+
+    fn common() -> T {
+        T::max_value()
+    }
+    fn is_common(value: &T) -> bool {
+        *value == T::max_value()
+    }
+}
+
+macro_rules! impl_const_bounds {
+    ($($t:ident)*) => {$(
+        impl ConstCommonality<$t> for MinCommonality {
+            const COMMON: $t = $t::MIN;
+        }
+        impl ConstCommonality<$t> for MaxCommonality {
+            const COMMON: $t = $t::MAX;
+        }
+    )*};
+}
+impl_const_bounds!(i8 i16 i32 i64 i128 isize u8 u16 u32 u64 u128 usize);
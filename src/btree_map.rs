@@ -3,17 +3,27 @@
 //! to be associated with a *common* value.
 
 use std::{
-    borrow::Borrow,
+    borrow::{Borrow, Cow},
     cmp::Ordering,
-    collections::{btree_map, BTreeMap},
-    fmt::{self, Debug, Formatter},
+    collections::{btree_map, BTreeMap, HashMap},
+    error::Error,
+    fmt::{self, Debug, Display, Formatter},
     hash::{Hash, Hasher},
     iter::FusedIterator,
+    marker::PhantomData,
     mem,
-    ops::{Deref, DerefMut, Index},
+    ops::{AddAssign, Bound, Deref, DerefMut, Index, RangeBounds, RangeFull},
 };
 
-use crate::{Commonality, DefaultCommonality, PhantomPtr};
+#[cfg(feature = "num-traits")]
+use num_traits::{CheckedSub, One};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::{
+    CommonValuePresent, Commonality, ConstCommonality, DefaultCommonality, Enumerable, Occupancy,
+    PhantomPtr,
+};
 
 // --------------------------------------------------------------------------
 
@@ -27,9 +37,10 @@ use crate::{Commonality, DefaultCommonality, PhantomPtr};
 /// like a collection (for example, [`len()`](Self::len) and [`iter()`](Self::iter)) operate only on
 /// the *uncommon* entries.
 pub struct TotalBTreeMap<K, V, C = DefaultCommonality> {
-    inner: BTreeMap<K, V>,
-    common: V, // need to store this value so we can return references to it, e.g., in Self::get
-    _commonality: PhantomPtr<C>,
+    pub(crate) inner: BTreeMap<K, V>,
+    // need to store this value so we can return references to it, e.g., in Self::get
+    pub(crate) common: V,
+    pub(crate) _commonality: PhantomPtr<C>,
 }
 
 impl<K: Clone, V: Clone, C> Clone for TotalBTreeMap<K, V, C> {
@@ -57,6 +68,55 @@ impl<K, V, C: Commonality<V>> TotalBTreeMap<K, V, C> {
         Self::default()
     }
 }
+impl<K, V, C: ConstCommonality<V>> TotalBTreeMap<K, V, C> {
+    /// Constructs a `TotalBTreeMap` in which all keys are associated with the *common* value, in a
+    /// `const` context.
+    ///
+    /// ```
+    /// # use std::sync::Mutex;
+    /// # use total_maps::{Commonality, ConstCommonality, TotalBTreeMap};
+    /// struct Unlimited;
+    /// impl Commonality<u32> for Unlimited {
+    ///     fn common() -> u32 {
+    ///         u32::MAX
+    ///     }
+    ///     fn is_common(value: &u32) -> bool {
+    ///         *value == u32::MAX
+    ///     }
+    /// }
+    /// impl ConstCommonality<u32> for Unlimited {
+    ///     const COMMON: u32 = u32::MAX;
+    /// }
+    ///
+    /// static QUOTAS: Mutex<TotalBTreeMap<&str, u32, Unlimited>> =
+    ///     Mutex::new(TotalBTreeMap::empty());
+    /// QUOTAS.lock().unwrap().insert("guest", 10);
+    /// assert_eq!(QUOTAS.lock().unwrap()["guest"], 10);
+    /// assert_eq!(QUOTAS.lock().unwrap()["admin"], u32::MAX);
+    /// ```
+    pub const fn empty() -> Self {
+        Self { inner: BTreeMap::new(), common: C::COMMON, _commonality: PhantomPtr::new() }
+    }
+}
+impl<K: Ord, V, C: Commonality<V>> TotalBTreeMap<K, V, C> {
+    /// Constructs a `TotalBTreeMap` from a vector of entries.
+    ///
+    /// If the vector contains several entries with the same key, the last one wins, as if the
+    /// entries had been inserted in order. Entries with *common* values are discarded. This sorts
+    /// the vector and builds the map in one pass, which is faster than inserting each entry.
+    pub fn from_vec(mut entries: Vec<(K, V)>) -> Self {
+        // Reversing first puts the last entry for each key first after the (stable) sort, so that
+        // it is the one kept by dedup_by
+        entries.reverse();
+        entries.sort_by(|(lhs, _), (rhs, _)| lhs.cmp(rhs));
+        entries.dedup_by(|(lhs, _), (rhs, _)| lhs == rhs);
+        Self {
+            inner: entries.into_iter().filter(|(_, value)| !C::is_common(value)).collect(),
+            common: C::common(),
+            _commonality: PhantomPtr::default(),
+        }
+    }
+}
 
 impl<K, V, C> TotalBTreeMap<K, V, C> {
     /// Returns the number of *uncommon* entries in the map.
@@ -71,6 +131,29 @@ impl<K, V, C> TotalBTreeMap<K, V, C> {
     pub fn clear(&mut self) {
         self.inner.clear()
     }
+
+    /// Returns the number of *uncommon* entries in the map. This is the same as
+    /// [`len()`](Self::len), spelled out for readers who might expect `len` to count every key.
+    ///
+    /// Every possible key has a value in a total map, so the logical size of its domain is the
+    /// number of values of `K`, which is usually unbounded. Neither method reports that.
+    pub fn effective_len(&self) -> usize {
+        self.len()
+    }
+    /// Returns true if every key is associated with the *common* value. This is the same as
+    /// [`is_empty()`](Self::is_empty).
+    pub fn is_trivial(&self) -> bool {
+        self.is_empty()
+    }
+    /// Returns a short human-readable description of the map, such as
+    /// `"3 uncommon entries, common = 0"`.
+    pub fn summary(&self) -> String
+    where
+        V: Debug,
+    {
+        let plural = if self.len() == 1 { "entry" } else { "entries" };
+        format!("{} uncommon {plural}, common = {:?}", self.len(), self.common)
+    }
 }
 
 // --------------------------------------------------------------------------
@@ -85,6 +168,28 @@ impl<K: Ord, V, C> TotalBTreeMap<K, V, C> {
     {
         self.inner.get(key).unwrap_or(&self.common)
     }
+    /// Returns the stored key and value of the given key's entry, if the key is associated with an
+    /// *uncommon* value. Keys associated with the *common* value have no stored key, so this
+    /// returns `None` for them.
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.inner.get_key_value(key)
+    }
+    /// Returns the value associated with the given key, borrowed from the map where possible.
+    ///
+    /// Unlike [get](Self::get), this allows the *common* value to be returned as an owned value, so
+    /// callers using it will be unaffected if the map stops storing its *common* value.
+    pub fn get_cow<Q>(&self, key: &Q) -> Cow<'_, V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        V: Clone,
+    {
+        Cow::Borrowed(self.get(key))
+    }
     /// Returns true if the map contains an *uncommon* entry with the given key.
     pub fn contains_key<Q>(&self, key: &Q) -> bool
     where
@@ -93,6 +198,78 @@ impl<K: Ord, V, C> TotalBTreeMap<K, V, C> {
     {
         self.inner.contains_key(key)
     }
+    /// Returns whether the given key is associated with an *uncommon* value stored in the map, or
+    /// with the implicit *common* value.
+    pub fn raw_occupancy<Q>(&self, key: &Q) -> Occupancy
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        if self.contains_key(key) {
+            Occupancy::UncommonStored
+        } else {
+            Occupancy::CommonImplicit
+        }
+    }
+    /// Returns references to the values associated with each of the given keys, in the same order.
+    pub fn get_all<'a, Q>(&'a self, keys: &[&Q]) -> Vec<&'a V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        keys.iter().map(|key| self.get(key)).collect()
+    }
+
+    /// Returns the *uncommon* entry with the smallest key above the given bound, if any.
+    ///
+    /// For example, `lower_bound(Bound::Included(&key))` returns the first *uncommon* entry at or
+    /// after `key`, and `lower_bound(Bound::Excluded(&key))` returns the first one strictly after
+    /// `key`.
+    pub fn lower_bound<Q>(&self, bound: Bound<&Q>) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.inner.range((bound, Bound::Unbounded)).next()
+    }
+    /// Returns the *uncommon* entry with the largest key below the given bound, if any.
+    ///
+    /// For example, `upper_bound(Bound::Included(&key))` returns the last *uncommon* entry at or
+    /// before `key`, and `upper_bound(Bound::Excluded(&key))` returns the last one strictly before
+    /// `key`.
+    pub fn upper_bound<Q>(&self, bound: Bound<&Q>) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.inner.range((Bound::Unbounded, bound)).next_back()
+    }
+
+    /// Treats the map as a right-continuous step function whose breakpoints are the *uncommon*
+    /// entries, and returns its value at the given key: the value of the greatest *uncommon* entry
+    /// whose key is at or before `key`, or the *common* value if there is no such entry.
+    ///
+    /// Unlike [`get()`](Self::get), this does not return the *common* value for keys between
+    /// breakpoints. Note that the function can never step back down to the *common* value, since
+    /// entries with *common* values are not stored.
+    pub fn value_at<Q>(&self, key: &Q) -> &V
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.upper_bound(Bound::Included(key)).map_or(&self.common, |(_, value)| value)
+    }
+
+    /// Returns the *uncommon* entry with the smallest key, or `None` if the map contains no
+    /// *uncommon* entries.
+    pub fn first_key_value(&self) -> Option<(&K, &V)> {
+        self.inner.first_key_value()
+    }
+    /// Returns the *uncommon* entry with the largest key, or `None` if the map contains no
+    /// *uncommon* entries.
+    pub fn last_key_value(&self) -> Option<(&K, &V)> {
+        self.inner.last_key_value()
+    }
 }
 
 impl<K: Borrow<Q> + Ord, Q: Ord + ?Sized, V, C> Index<&Q> for TotalBTreeMap<K, V, C> {
@@ -120,21 +297,215 @@ impl<K: Ord, V, C: Commonality<V>> TotalBTreeMap<K, V, C> {
         self.inner.remove(key).unwrap_or_else(C::common)
     }
 
+    /// Associates a key with the *common* value in the map, and returns the stored key and the
+    /// value previously associated with it if that value was *uncommon*, or `None` if it was
+    /// *common*.
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.inner.remove_entry(key)
+    }
+    /// Resets the *uncommon* entry with the smallest key to the *common* value, and returns its
+    /// key and the value previously associated with it, or `None` if the map contains no
+    /// *uncommon* entries.
+    pub fn pop_first(&mut self) -> Option<(K, V)> {
+        self.inner.pop_first()
+    }
+    /// Resets the *uncommon* entry with the largest key to the *common* value, and returns its key
+    /// and the value previously associated with it, or `None` if the map contains no *uncommon*
+    /// entries.
+    pub fn pop_last(&mut self) -> Option<(K, V)> {
+        self.inner.pop_last()
+    }
+
+    /// Splits the map in two at the given key. All *uncommon* entries with keys at or after `key`
+    /// are moved into the returned map, and the rest stay in this map. Both maps share the same
+    /// *common* value.
+    pub fn split_off<Q>(&mut self, key: &Q) -> Self
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        TotalBTreeMap {
+            inner: self.inner.split_off(key),
+            common: C::common(),
+            _commonality: PhantomPtr::default(),
+        }
+    }
+
+    /// Moves all *uncommon* entries from `other` into this map, leaving `other` with no *uncommon*
+    /// entries. If a key has an *uncommon* value in both maps, the value from `other` wins, as if
+    /// each of its entries had been inserted into this map.
+    pub fn append(&mut self, other: &mut Self) {
+        self.inner.append(&mut other.inner)
+    }
+
+    /// Associates a key with a value in the map, and returns the value previously associated with
+    /// that key if it was *uncommon*. If it was *common*, returns
+    /// [`C::try_common()`](Commonality::try_common), which is `None` unless the commonality
+    /// provides the *common* value cheaply.
+    ///
+    /// Unlike [`insert()`](Self::insert), this method never calls
+    /// [`C::common()`](Commonality::common), which makes it preferable when the *common* value is
+    /// expensive to construct and the previous value is not needed.
+    pub fn insert_opt(&mut self, key: K, value: V) -> Option<V> {
+        if C::is_common(&value) { self.inner.remove(&key) } else { self.inner.insert(key, value) }
+            .or_else(C::try_common)
+    }
+
+    /// Associates a key with a value in the map, unless the key is already associated with an
+    /// *uncommon* value, and returns a reference to the value now associated with the key.
+    ///
+    /// Keys associated with the *common* value count as vacant. If `value` is *common*, this
+    /// succeeds for such a key and leaves it associated with the *common* value. If the key is
+    /// associated with an *uncommon* value, the map is left unchanged, and the returned error
+    /// contains the key's entry and the value that was not inserted.
+    pub fn try_insert(&mut self, key: K, value: V) -> Result<&V, OccupiedError<'_, K, V, C>> {
+        match self.inner.entry(key) {
+            btree_map::Entry::Occupied(inner) => Err(OccupiedError {
+                entry: Entry {
+                    inner: EntryInner::Occupied { inner },
+                    _commonality: PhantomPtr::default(),
+                },
+                value,
+            }),
+            btree_map::Entry::Vacant(_) if C::is_common(&value) => Ok(&self.common),
+            btree_map::Entry::Vacant(inner) => Ok(inner.insert(value)),
+        }
+    }
+
+    /// Associates a key with the *common* value in the map, and returns the value previously
+    /// associated with that key if it was *uncommon*. If it was *common*, returns
+    /// [`C::try_common()`](Commonality::try_common), which is `None` unless the commonality
+    /// provides the *common* value cheaply.
+    ///
+    /// Unlike [`remove()`](Self::remove), this method never calls
+    /// [`C::common()`](Commonality::common).
+    pub fn remove_opt<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.inner.remove(key).or_else(C::try_common)
+    }
+
+    /// Returns true if the given value is *uncommon*, meaning that inserting it would store an
+    /// entry in the map.
+    pub fn would_store(&self, value: &V) -> bool {
+        !C::is_common(value)
+    }
+
+    /// Returns the number of *uncommon* entries the map would contain after associating the given
+    /// key with the given value, without modifying the map.
+    pub fn len_after_insert<Q>(&self, key: &Q, value: &V) -> usize
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        match (self.contains_key(key), self.would_store(value)) {
+            (false, true) => self.len() + 1,
+            (true, false) => self.len() - 1,
+            _ => self.len(),
+        }
+    }
+
     /// Gets the given key's associated entry in the map for in-place manipulation.
+    ///
+    /// Unless the commonality is [CHEAP](Commonality::CHEAP), the *common* value is not
+    /// constructed for a vacant entry until the entry is modified.
     pub fn entry(&mut self, key: K) -> Entry<'_, K, V, C> {
         Entry {
             inner: match self.inner.entry(key) {
                 btree_map::Entry::Occupied(inner) => EntryInner::Occupied { inner },
-                btree_map::Entry::Vacant(inner) => EntryInner::Vacant { inner, value: C::common() },
+                btree_map::Entry::Vacant(inner) if C::CHEAP => {
+                    EntryInner::Vacant { inner, value: C::common() }
+                }
+                btree_map::Entry::Vacant(inner) => {
+                    EntryInner::Implicit { inner, common: &self.common }
+                }
             },
             _commonality: PhantomPtr::default(),
         }
     }
+
+    /// Gets the given key's associated entry in the map for in-place manipulation, if the key is
+    /// associated with an *uncommon* value. Otherwise, returns the key back to the caller, without
+    /// constructing the *common* value.
+    ///
+    /// This is useful when the key is expensive to construct and usually associated with the
+    /// *common* value, since the key can be reused if no entry is needed:
+    ///
+    /// ```
+    /// # use total_maps::TotalBTreeMap;
+    /// let mut counts = TotalBTreeMap::<String, u32>::new();
+    /// counts.insert("foo".to_owned(), 1);
+    /// for name in ["foo", "bar"] {
+    ///     match counts.entry_or_return(name.to_owned()) {
+    ///         Ok(mut count) => *count += 1,
+    ///         Err(name) => println!("no entry for {name}"),
+    ///     }
+    /// }
+    /// assert_eq!(counts.get("foo"), &2);
+    /// assert!(!counts.contains_key("bar"));
+    /// ```
+    pub fn entry_or_return(&mut self, key: K) -> Result<Entry<'_, K, V, C>, K> {
+        match self.inner.entry(key) {
+            btree_map::Entry::Occupied(inner) => Ok(Entry {
+                inner: EntryInner::Occupied { inner },
+                _commonality: PhantomPtr::default(),
+            }),
+            btree_map::Entry::Vacant(inner) => Err(inner.into_key()),
+        }
+    }
+
+    /// Gets a mutable view of the value associated with the given key, if the key is associated
+    /// with an *uncommon* value. Unlike [`entry()`](Self::entry), this borrows the key rather than
+    /// taking ownership of it.
+    ///
+    /// The value stays in the map while the returned view exists. When the view is dropped, the
+    /// entry is removed if its value is then *common*. See [RefMut] for details.
+    pub fn get_mut<'a, 'b, Q>(&'a mut self, key: &'b Q) -> Option<RefMut<'a, 'b, K, Q, V, C>>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        if !self.inner.contains_key(key) {
+            return None;
+        }
+        Some(RefMut { map: self, key })
+    }
+
+    /// Gets the *uncommon* entry with the smallest key for in-place manipulation, or `None` if the
+    /// map contains no *uncommon* entries.
+    pub fn first_entry(&mut self) -> Option<Entry<'_, K, V, C>> {
+        let inner = self.inner.first_entry()?;
+        Some(Entry { inner: EntryInner::Occupied { inner }, _commonality: PhantomPtr::default() })
+    }
+    /// Gets the *uncommon* entry with the largest key for in-place manipulation, or `None` if the
+    /// map contains no *uncommon* entries.
+    pub fn last_entry(&mut self) -> Option<Entry<'_, K, V, C>> {
+        let inner = self.inner.last_entry()?;
+        Some(Entry { inner: EntryInner::Occupied { inner }, _commonality: PhantomPtr::default() })
+    }
 }
 
 /// A view into a single entry in a [TotalBTreeMap].
 ///
-/// This view is constructed from [TotalBTreeMap::entry].
+/// The entry's value may be changed freely through the view. When the view is dropped, the entry
+/// is removed from the map if its value is *common*, or stored if it is *uncommon*. This also
+/// happens if the view is dropped while unwinding from a panic, so the map remains valid (holding
+/// whatever value the entry had when the panic occurred) if the panic is caught. However, if the
+/// [commonality](Commonality) itself panics during unwinding, the process aborts.
+///
+/// There is deliberately no method that returns a bare `&mut V` defaulting to the *common* value:
+/// the map couldn't tell whether the value was left *common*, and pruning it lazily would break
+/// the invariant that [len](TotalBTreeMap::len) and iteration rely on. An entry whose value is
+/// never changed is not stored at all, so `*map.entry(key)` serves the same purpose.
+///
+/// This view is constructed from [TotalBTreeMap::entry], [TotalBTreeMap::entry_or_return],
+/// [TotalBTreeMap::first_entry], or [TotalBTreeMap::last_entry].
 pub struct Entry<'a, K: Ord, V, C: Commonality<V> = DefaultCommonality> {
     inner: EntryInner<'a, K, V>,
     _commonality: PhantomPtr<C>,
@@ -146,16 +517,26 @@ impl<K: Ord, V, C: Commonality<V>> Deref for Entry<'_, K, V, C> {
         match &self.inner {
             EntryInner::Occupied { inner } => inner.get(),
             EntryInner::Vacant { value, .. } => value,
+            EntryInner::Implicit { common, .. } => common,
             EntryInner::Dropping => unreachable!(),
         }
     }
 }
 impl<K: Ord, V, C: Commonality<V>> DerefMut for Entry<'_, K, V, C> {
     fn deref_mut(&mut self) -> &mut Self::Target {
+        if let EntryInner::Implicit { .. } = self.inner {
+            let value = C::common();
+            let EntryInner::Implicit { inner, .. } =
+                mem::replace(&mut self.inner, EntryInner::Dropping)
+            else {
+                unreachable!();
+            };
+            self.inner = EntryInner::Vacant { inner, value };
+        }
         match &mut self.inner {
             EntryInner::Occupied { inner } => inner.get_mut(),
             EntryInner::Vacant { value, .. } => value,
-            EntryInner::Dropping => unreachable!(),
+            EntryInner::Implicit { .. } | EntryInner::Dropping => unreachable!(),
         }
     }
 }
@@ -173,6 +554,7 @@ impl<K: Ord, V, C: Commonality<V>> Drop for Entry<'_, K, V, C> {
                     inner.insert(value);
                 }
             }
+            EntryInner::Implicit { .. } => {}
             EntryInner::Dropping => unreachable!(),
         }
     }
@@ -184,18 +566,360 @@ impl<'a, K: Debug + Ord, V: Debug, C: Commonality<V>> Debug for Entry<'a, K, V,
         match &self.inner {
             EntryInner::Occupied { inner } => f.field(inner.key()).field(inner.get()),
             EntryInner::Vacant { inner, value } => f.field(inner.key()).field(value),
+            EntryInner::Implicit { inner, common } => f.field(inner.key()).field(common),
             EntryInner::Dropping => &mut f,
         };
         f.finish()
     }
 }
 
+/// The error returned by [TotalBTreeMap::try_insert] when the key is already associated with an
+/// *uncommon* value.
+pub struct OccupiedError<'a, K: Ord, V, C: Commonality<V> = DefaultCommonality> {
+    /// The entry for the key, which holds its existing *uncommon* value.
+    pub entry: Entry<'a, K, V, C>,
+    /// The value that was not inserted.
+    pub value: V,
+}
+
+impl<K: Debug + Ord, V: Debug, C: Commonality<V>> Debug for OccupiedError<'_, K, V, C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OccupiedError")
+            .field("entry", &self.entry)
+            .field("value", &self.value)
+            .finish()
+    }
+}
+impl<K: Ord, V, C: Commonality<V>> Display for OccupiedError<'_, K, V, C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "key is already associated with an uncommon value")
+    }
+}
+impl<K: Debug + Ord, V: Debug, C: Commonality<V>> Error for OccupiedError<'_, K, V, C> {}
+
 enum EntryInner<'a, K, V> {
     Occupied { inner: btree_map::OccupiedEntry<'a, K, V> },
     Vacant { inner: btree_map::VacantEntry<'a, K, V>, value: V },
+    // A vacant entry whose value is still the *common* value, which hasn't been constructed yet
+    Implicit { inner: btree_map::VacantEntry<'a, K, V>, common: &'a V },
     Dropping,
 }
 
+impl<K: Ord, V, C: Commonality<V>> TotalBTreeMap<K, V, C> {
+    /// Gets the given key's associated entry in the map for in-place manipulation, without taking
+    /// ownership of the key.
+    ///
+    /// The key is converted into an owned key only if a new *uncommon* entry is stored when the
+    /// returned view is dropped. See [EntryRef] for details.
+    pub fn entry_ref<'a, 'b, Q>(&'a mut self, key: &'b Q) -> EntryRef<'a, 'b, K, Q, V, C>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ToOwned<Owned = K> + ?Sized,
+    {
+        self.entry_ref_with(key, C::common)
+    }
+    /// Gets the given key's associated entry in the map for in-place manipulation, without taking
+    /// ownership of the key. If the key is associated with the *common* value, the entry's value
+    /// is initialized to `default` instead.
+    ///
+    /// The key is converted into an owned key only if a new *uncommon* entry is stored when the
+    /// returned view is dropped. See [EntryRef] for details.
+    pub fn entry_ref_or_insert<'a, 'b, Q>(
+        &'a mut self,
+        key: &'b Q,
+        default: V,
+    ) -> EntryRef<'a, 'b, K, Q, V, C>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ToOwned<Owned = K> + ?Sized,
+    {
+        self.entry_ref_with(key, || default)
+    }
+    // Only calls `default` if the key is associated with the *common* value
+    fn entry_ref_with<'a, 'b, Q>(
+        &'a mut self,
+        key: &'b Q,
+        default: impl FnOnce() -> V,
+    ) -> EntryRef<'a, 'b, K, Q, V, C>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ToOwned<Owned = K> + ?Sized,
+    {
+        let vacant = if self.inner.contains_key(key) { None } else { Some(default()) };
+        EntryRef { map: self, key, vacant }
+    }
+}
+
+/// A view into a single entry in a [TotalBTreeMap], identified by a borrowed key.
+///
+/// Like an [Entry], the view dereferences to the entry's value, which may be changed freely. When
+/// the view is dropped, the entry is removed from the map if its value is *common*, or stored if it
+/// is *uncommon*; only in the latter case, and only if the map didn't already contain an entry for
+/// the key, is the borrowed key converted into an owned key.
+///
+/// Each access to an entry that is stored in the map looks the key up again.
+///
+/// This view is constructed from [TotalBTreeMap::entry_ref] or
+/// [TotalBTreeMap::entry_ref_or_insert].
+pub struct EntryRef<'a, 'b, K, Q, V, C = DefaultCommonality>
+where
+    K: Ord + Borrow<Q>,
+    Q: Ord + ToOwned<Owned = K> + ?Sized,
+    C: Commonality<V>,
+{
+    map: &'a mut TotalBTreeMap<K, V, C>,
+    key: &'b Q,
+    vacant: Option<V>, // the entry's value, if the map doesn't contain an entry for the key
+}
+
+impl<K, Q, V, C> Deref for EntryRef<'_, '_, K, Q, V, C>
+where
+    K: Ord + Borrow<Q>,
+    Q: Ord + ToOwned<Owned = K> + ?Sized,
+    C: Commonality<V>,
+{
+    type Target = V;
+    fn deref(&self) -> &Self::Target {
+        match &self.vacant {
+            Some(value) => value,
+            None => &self.map.inner[self.key],
+        }
+    }
+}
+impl<K, Q, V, C> DerefMut for EntryRef<'_, '_, K, Q, V, C>
+where
+    K: Ord + Borrow<Q>,
+    Q: Ord + ToOwned<Owned = K> + ?Sized,
+    C: Commonality<V>,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        match &mut self.vacant {
+            Some(value) => value,
+            None => self.map.inner.get_mut(self.key).unwrap(),
+        }
+    }
+}
+
+impl<K, Q, V, C> Drop for EntryRef<'_, '_, K, Q, V, C>
+where
+    K: Ord + Borrow<Q>,
+    Q: Ord + ToOwned<Owned = K> + ?Sized,
+    C: Commonality<V>,
+{
+    fn drop(&mut self) {
+        match self.vacant.take() {
+            Some(value) => {
+                if !C::is_common(&value) {
+                    self.map.inner.insert(self.key.to_owned(), value);
+                }
+            }
+            None => {
+                if C::is_common(&self.map.inner[self.key]) {
+                    self.map.inner.remove(self.key);
+                }
+            }
+        }
+    }
+}
+
+impl<K, Q, V, C> Debug for EntryRef<'_, '_, K, Q, V, C>
+where
+    K: Ord + Borrow<Q>,
+    Q: Ord + ToOwned<Owned = K> + Debug + ?Sized,
+    V: Debug,
+    C: Commonality<V>,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("EntryRef").field(&self.key).field(&**self).finish()
+    }
+}
+
+/// A mutable view of the value of an *uncommon* entry in a [TotalBTreeMap], identified by a
+/// borrowed key.
+///
+/// The view dereferences to the entry's value, which stays in the map and may be changed freely.
+/// When the view is dropped, the entry is removed from the map if its value is *common*. Each
+/// access looks the key up again.
+///
+/// If the view is leaked after its value is set to the *common* value, the entry remains stored,
+/// and the map must be fixed with [`repair()`](TotalBTreeMap::repair); no *uncommon* value is lost.
+///
+/// This view is constructed from [TotalBTreeMap::get_mut].
+pub struct RefMut<'a, 'b, K, Q, V, C = DefaultCommonality>
+where
+    K: Ord + Borrow<Q>,
+    Q: Ord + ?Sized,
+    C: Commonality<V>,
+{
+    map: &'a mut TotalBTreeMap<K, V, C>,
+    key: &'b Q,
+}
+
+impl<K, Q, V, C> Deref for RefMut<'_, '_, K, Q, V, C>
+where
+    K: Ord + Borrow<Q>,
+    Q: Ord + ?Sized,
+    C: Commonality<V>,
+{
+    type Target = V;
+    fn deref(&self) -> &Self::Target {
+        &self.map.inner[self.key]
+    }
+}
+impl<K, Q, V, C> DerefMut for RefMut<'_, '_, K, Q, V, C>
+where
+    K: Ord + Borrow<Q>,
+    Q: Ord + ?Sized,
+    C: Commonality<V>,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.map.inner.get_mut(self.key).unwrap()
+    }
+}
+
+impl<K, Q, V, C> Drop for RefMut<'_, '_, K, Q, V, C>
+where
+    K: Ord + Borrow<Q>,
+    Q: Ord + ?Sized,
+    C: Commonality<V>,
+{
+    fn drop(&mut self) {
+        if C::is_common(&self.map.inner[self.key]) {
+            self.map.inner.remove(self.key);
+        }
+    }
+}
+
+impl<K, Q, V, C> Debug for RefMut<'_, '_, K, Q, V, C>
+where
+    K: Ord + Borrow<Q>,
+    Q: Ord + Debug + ?Sized,
+    V: Debug,
+    C: Commonality<V>,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("RefMut").field(&self.key).field(&**self).finish()
+    }
+}
+
+// --------------------------------------------------------------------------
+// Accumulation
+
+impl<K: Ord, V: AddAssign, C: Commonality<V>> TotalBTreeMap<K, V, C> {
+    /// Adds `delta` to the value associated with the given key. If the sum is *common*, the key is
+    /// reset to the *common* value.
+    pub fn add(&mut self, key: K, delta: V) {
+        *self.entry(key) += delta;
+    }
+}
+
+#[cfg(feature = "num-traits")]
+impl<K: Ord, V: AddAssign + One, C: Commonality<V>> TotalBTreeMap<K, V, C> {
+    /// Adds one to the value associated with the given key. If the sum is *common*, the key is
+    /// reset to the *common* value.
+    pub fn increment(&mut self, key: K) {
+        self.add(key, V::one());
+    }
+}
+
+#[cfg(feature = "num-traits")]
+impl<K: Ord, V: CheckedSub, C: Commonality<V>> TotalBTreeMap<K, V, C> {
+    /// Subtracts `delta` from the value associated with the given key. If the difference is
+    /// *common*, the key is reset to the *common* value.
+    ///
+    /// Returns `None`, leaving the map unchanged, if the subtraction would overflow (for example,
+    /// if it would take an unsigned value below zero). Otherwise, returns true if the key is still
+    /// associated with an *uncommon* value.
+    pub fn sub(&mut self, key: K, delta: V) -> Option<bool> {
+        let mut entry = self.entry(key);
+        *entry = entry.checked_sub(&delta)?;
+        Some(!C::is_common(&entry))
+    }
+}
+
+#[cfg(feature = "num-traits")]
+impl<K: Ord, V: CheckedSub + One, C: Commonality<V>> TotalBTreeMap<K, V, C> {
+    /// Subtracts one from the value associated with the given key. If the difference is *common*,
+    /// the key is reset to the *common* value.
+    ///
+    /// Returns `None`, leaving the map unchanged, if the subtraction would overflow. Otherwise,
+    /// returns true if the key is still associated with an *uncommon* value.
+    pub fn decrement(&mut self, key: K) -> Option<bool> {
+        self.sub(key, V::one())
+    }
+}
+
+// --------------------------------------------------------------------------
+// Filtering
+
+impl<K: Ord, V, C> TotalBTreeMap<K, V, C> {
+    /// Resets all *uncommon* entries for which the predicate returns true to the *common* value.
+    pub fn reset_where<F: FnMut(&K, &V) -> bool>(&mut self, mut pred: F) {
+        self.inner.retain(|key, value| !pred(key, value))
+    }
+}
+impl<K: Ord, V, C: Commonality<V>> TotalBTreeMap<K, V, C> {
+    /// Retains only the *uncommon* entries for which the predicate returns true, resetting the
+    /// others to the *common* value. Entries are visited in ascending key order.
+    ///
+    /// The predicate may mutate each value. An entry whose value is *common* after the predicate
+    /// returns is reset to the *common* value, even if the predicate returned true.
+    pub fn retain<F: FnMut(&K, &mut V) -> bool>(&mut self, mut f: F) {
+        self.inner.retain(|key, value| f(key, value) && !C::is_common(value))
+    }
+
+    /// Creates an iterator that visits all *uncommon* entries in ascending key order, and removes
+    /// and yields those for which the predicate returns true, resetting them to the *common* value.
+    ///
+    /// The predicate may mutate each value. An entry whose value is *common* after the predicate
+    /// returns is reset to the *common* value regardless of the result, and is never yielded.
+    ///
+    /// Entries are only visited as the iterator advances; if it is dropped before being fully
+    /// consumed, the remaining entries are left in the map untouched.
+    pub fn extract_if<'a, F>(
+        &'a mut self,
+        mut pred: F,
+    ) -> ExtractIf<'a, K, V, impl FnMut(&K, &mut V) -> bool + 'a>
+    where
+        F: FnMut(&K, &mut V) -> bool + 'a,
+    {
+        ExtractIf {
+            inner: self
+                .inner
+                .extract_if(.., move |key, value| pred(key, value) || C::is_common(value)),
+            is_common: C::is_common,
+        }
+    }
+
+    /// Retains only the given keys' entries for which the predicate returns true, resetting the
+    /// others to the *common* value. Entries for keys not in `keys` are left untouched.
+    ///
+    /// Unlike a whole-map retain, the predicate is also called for keys associated with the
+    /// *common* value, and may mutate that value to make the entry *uncommon*. Any entry whose
+    /// value is *common* after the predicate returns is reset to the *common* value.
+    pub fn retain_keys<I, F>(&mut self, keys: I, mut f: F)
+    where
+        I: IntoIterator<Item = K>,
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        for key in keys {
+            match self.inner.get_mut(&key) {
+                Some(value) => {
+                    if !f(&key, value) || C::is_common(value) {
+                        self.inner.remove(&key);
+                    }
+                }
+                None => {
+                    let mut value = C::common();
+                    if f(&key, &mut value) && !C::is_common(&value) {
+                        self.inner.insert(key, value);
+                    }
+                }
+            }
+        }
+    }
+}
+
 // --------------------------------------------------------------------------
 // Iteration
 
@@ -209,6 +933,12 @@ impl<K, V, C> TotalBTreeMap<K, V, C> {
     pub fn into_keys(self) -> IntoKeys<K, V> {
         IntoKeys(self.inner.into_keys())
     }
+    /// Returns all keys associated with *uncommon* values in the map, in sorted order. Viewing the
+    /// map as a function, this is its support; for a sparse vector, it is the set of nonzero
+    /// indices.
+    pub fn support(&self) -> Vec<&K> {
+        self.keys().collect()
+    }
     /// An iterator over all *uncommon* values in the map, in sorted order.
     pub fn values(&self) -> Values<'_, K, V> {
         Values(self.inner.values())
@@ -221,9 +951,40 @@ impl<K, V, C> TotalBTreeMap<K, V, C> {
     pub fn iter(&self) -> Iter<'_, K, V> {
         Iter(self.inner.iter())
     }
+    /// Returns the full contents of the map: the *common* value, and an iterator over all
+    /// *uncommon* entries in sorted order. All keys not produced by the iterator are associated
+    /// with the *common* value.
+    pub fn describe(&self) -> (&V, Iter<'_, K, V>) {
+        (&self.common, self.iter())
+    }
+    /// Resets all entries in the map to the *common* value, and returns all previously *uncommon*
+    /// entries as an iterator, in sorted order.
+    ///
+    /// The map is reset even if the returned iterator is dropped before being fully consumed; any
+    /// remaining entries are dropped along with it.
+    pub fn drain(&mut self) -> Drain<'_, K, V> {
+        Drain(mem::take(&mut self.inner).into_iter(), PhantomData)
+    }
 }
 
-impl<K, V, C> IntoIterator for TotalBTreeMap<K, V, C> {
+impl<K: Ord, V, C: Commonality<V>> TotalBTreeMap<K, V, C> {
+    /// A lending iterator over mutable references to all *uncommon* values in the map, in sorted
+    /// order. See [IterMut] for details.
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V, C> {
+        ValuesMut(self.iter_mut())
+    }
+    /// A lending iterator over all *uncommon* entries in the map, with mutable references to the
+    /// values, in sorted order. See [IterMut] for details.
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V, C> {
+        let map = &mut self.inner as *mut BTreeMap<K, V>;
+        // SAFETY: the map outlives the iterator, which borrows it mutably for its whole lifetime;
+        // the inner iterator is dropped before the map is used again through this pointer
+        let iter = unsafe { (*map).iter_mut() };
+        IterMut { map, iter: Some(iter), touched: false, _commonality: PhantomPtr::default() }
+    }
+}
+
+impl<K, V, C> IntoIterator for TotalBTreeMap<K, V, C> {
     type Item = (K, V);
     type IntoIter = IntoIter<K, V>;
     fn into_iter(self) -> Self::IntoIter {
@@ -448,6 +1209,582 @@ impl<K, V> ExactSizeIterator for IntoIter<K, V> {
 }
 impl<K, V> FusedIterator for IntoIter<K, V> {}
 
+/// A draining iterator over the *uncommon* entries in a [TotalBTreeMap].
+///
+/// This iterator is created by [TotalBTreeMap::drain]. It yields entries in sorted order. The map
+/// is emptied as soon as the iterator is created, so entries that have not yet been yielded are
+/// dropped along with the iterator.
+pub struct Drain<'a, K, V>(btree_map::IntoIter<K, V>, PhantomData<&'a mut BTreeMap<K, V>>);
+impl<K, V> Default for Drain<'_, K, V> {
+    fn default() -> Self {
+        Self(Default::default(), PhantomData)
+    }
+}
+impl<K, V> Iterator for Drain<'_, K, V> {
+    type Item = (K, V);
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+impl<K, V> DoubleEndedIterator for Drain<'_, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back()
+    }
+}
+impl<K, V> ExactSizeIterator for Drain<'_, K, V> {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+impl<K, V> FusedIterator for Drain<'_, K, V> {}
+
+/// An iterator that removes and yields the *uncommon* entries of a [TotalBTreeMap] that match a
+/// predicate.
+///
+/// This iterator is created by [TotalBTreeMap::extract_if]. It yields entries in sorted order.
+/// `F` is the type of the predicate, including the check that resets entries whose values were
+/// made *common*.
+pub struct ExtractIf<'a, K, V, F: FnMut(&K, &mut V) -> bool> {
+    inner: btree_map::ExtractIf<'a, K, V, RangeFull, F>,
+    is_common: fn(&V) -> bool,
+}
+impl<K: Ord, V, F: FnMut(&K, &mut V) -> bool> Iterator for ExtractIf<'_, K, V, F> {
+    type Item = (K, V);
+    fn next(&mut self) -> Option<Self::Item> {
+        let is_common = self.is_common;
+        self.inner.find(|(_, value)| !is_common(value))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.inner.size_hint().1)
+    }
+}
+impl<K: Ord, V, F: FnMut(&K, &mut V) -> bool> FusedIterator for ExtractIf<'_, K, V, F> {}
+impl<K, V, F: FnMut(&K, &mut V) -> bool> Debug for ExtractIf<'_, K, V, F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExtractIf").finish_non_exhaustive()
+    }
+}
+
+/// A lending iterator over the *uncommon* entries in a [TotalBTreeMap], with mutable references to
+/// their values.
+///
+/// This is not an [Iterator]: each entry returned by [`next()`](Self::next) borrows the iterator,
+/// and so must be released before the next entry is requested. The values may be changed
+/// freely, including to the *common* value. The entries stay in the map while the iterator exists;
+/// when the iterator is dropped, if any entry was visited, the map is scanned once and every entry
+/// whose value is now *common* is removed. If the iterator is leaked after a value is set to the
+/// *common* value, that entry remains stored, and the map must be fixed with
+/// [`repair()`](TotalBTreeMap::repair); no *uncommon* value is lost.
+///
+/// This iterator is created by [TotalBTreeMap::iter_mut]. It yields entries in sorted order.
+pub struct IterMut<'a, K: Ord, V, C: Commonality<V> = DefaultCommonality> {
+    // BTreeMap's cursor API is unstable, so the map is reached through a pointer once the inner
+    // iterator is done with it.
+    map: *mut BTreeMap<K, V>,
+    iter: Option<btree_map::IterMut<'a, K, V>>,
+    touched: bool,
+    _commonality: PhantomPtr<C>,
+}
+
+// SAFETY: the iterator acts as a `&mut BTreeMap<K, V>`
+unsafe impl<K: Ord + Send, V: Send, C: Commonality<V>> Send for IterMut<'_, K, V, C> {}
+unsafe impl<K: Ord + Sync, V: Sync, C: Commonality<V>> Sync for IterMut<'_, K, V, C> {}
+
+impl<K: Ord, V, C: Commonality<V>> IterMut<'_, K, V, C> {
+    /// Advances the iterator and returns the next entry, with a mutable reference to its value, or
+    /// `None` once all entries have been visited.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<(&K, &mut V)> {
+        let entry = self.iter.as_mut()?.next()?;
+        self.touched = true;
+        Some(entry)
+    }
+    /// Returns the number of entries that have not yet been visited.
+    pub fn len(&self) -> usize {
+        self.iter.as_ref().map_or(0, ExactSizeIterator::len)
+    }
+    /// Returns true if all entries have been visited.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<K: Ord, V, C: Commonality<V>> Drop for IterMut<'_, K, V, C> {
+    fn drop(&mut self) {
+        self.iter = None;
+        if self.touched {
+            // SAFETY: the inner iterator, and every reference it handed out, is gone
+            unsafe { &mut *self.map }.retain(|_, value| !C::is_common(value));
+        }
+    }
+}
+
+impl<K: Ord + Debug, V: Debug, C: Commonality<V>> Debug for IterMut<'_, K, V, C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("IterMut").field(&self.iter).finish()
+    }
+}
+
+/// A lending iterator over mutable references to the *uncommon* values in a [TotalBTreeMap].
+///
+/// This iterator is created by [TotalBTreeMap::values_mut]. It behaves like an [IterMut] that
+/// doesn't expose the keys.
+pub struct ValuesMut<'a, K: Ord, V, C: Commonality<V> = DefaultCommonality>(IterMut<'a, K, V, C>);
+
+impl<K: Ord, V, C: Commonality<V>> ValuesMut<'_, K, V, C> {
+    /// Advances the iterator and returns a mutable reference to the next value, or `None` once all
+    /// values have been visited.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<&mut V> {
+        self.0.next().map(|(_, value)| value)
+    }
+    /// Returns the number of values that have not yet been visited.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+    /// Returns true if all values have been visited.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl<K: Ord, V: Debug, C: Commonality<V>> Debug for ValuesMut<'_, K, V, C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ValuesMut").field("len", &self.len()).finish_non_exhaustive()
+    }
+}
+
+// --------------------------------------------------------------------------
+// Cursors
+
+impl<K: Ord + Clone, V, C: Commonality<V>> TotalBTreeMap<K, V, C> {
+    /// Returns a cursor positioned at the *uncommon* entry with the smallest key above the given
+    /// bound, or at the ghost position if there is no such entry.
+    ///
+    /// See [CursorMut] for details.
+    pub fn lower_bound_mut<Q>(&mut self, bound: Bound<&Q>) -> CursorMut<'_, K, V, C>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let current = self.lower_bound(bound).map(|(key, _)| key.clone());
+        CursorMut { map: &mut self.inner, current, _commonality: PhantomPtr::default() }
+    }
+    /// Returns a cursor positioned at the *uncommon* entry with the largest key below the given
+    /// bound, or at the ghost position if there is no such entry.
+    ///
+    /// See [CursorMut] for details.
+    pub fn upper_bound_mut<Q>(&mut self, bound: Bound<&Q>) -> CursorMut<'_, K, V, C>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let current = self.upper_bound(bound).map(|(key, _)| key.clone());
+        CursorMut { map: &mut self.inner, current, _commonality: PhantomPtr::default() }
+    }
+
+    /// Returns a streaming iterator over the *uncommon* entries in the map whose keys lie within
+    /// the given range, in sorted order, which allows their values to be modified.
+    ///
+    /// See [RangeMut] for details.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start of the range is greater than its end, or if the start and end are equal
+    /// and both excluded.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use total_maps::TotalBTreeMap;
+    /// let mut m = (0..10).map(|i| (i, i)).collect::<TotalBTreeMap<_, _>>();
+    /// let mut range = m.range_mut(3..6);
+    /// while let Some((_, value)) = range.next() {
+    ///     *value = 0;
+    /// }
+    /// drop(range);
+    /// assert_eq!(m.len(), 6);
+    /// assert!(!m.contains_key(&4));
+    /// ```
+    pub fn range_mut<R: RangeBounds<K>>(&mut self, range: R) -> RangeMut<'_, K, V, C, R> {
+        check_range(&range);
+        RangeMut {
+            map: &mut self.inner,
+            range,
+            current: None,
+            finished: false,
+            _commonality: PhantomPtr::default(),
+        }
+    }
+}
+
+/// A cursor over the *uncommon* entries in a [TotalBTreeMap], which can be moved back and forth
+/// in key order and can modify or remove the entry it points to.
+///
+/// A cursor is either positioned at an *uncommon* entry or at the *ghost* position, which lies
+/// between the last and the first entries. Moving forward from the ghost position moves to the
+/// first entry, and moving backward moves to the last.
+///
+/// The value of the current entry can be set to the *common* value via
+/// [`value_mut()`](Self::value_mut). The map's invariant is then restored at well-defined points:
+/// when the cursor moves off the entry (via [`move_next()`](Self::move_next) or
+/// [`move_prev()`](Self::move_prev)) or is dropped, the entry is removed if its value is *common*.
+///
+/// The cursor keeps a clone of the current entry's key, and each movement looks up the adjacent
+/// entry in the map, taking logarithmic time.
+///
+/// This cursor is created by [TotalBTreeMap::lower_bound_mut] or [TotalBTreeMap::upper_bound_mut].
+pub struct CursorMut<'a, K: Ord + Clone, V, C: Commonality<V> = DefaultCommonality> {
+    map: &'a mut BTreeMap<K, V>,
+    current: Option<K>,
+    _commonality: PhantomPtr<C>,
+}
+
+impl<K: Ord + Clone, V, C: Commonality<V>> CursorMut<'_, K, V, C> {
+    /// Returns the key of the current entry, or `None` if the cursor is at the ghost position.
+    pub fn key(&self) -> Option<&K> {
+        self.current.as_ref()
+    }
+    /// Returns the value of the current entry, or `None` if the cursor is at the ghost position.
+    pub fn value(&self) -> Option<&V> {
+        self.current.as_ref().and_then(|key| self.map.get(key))
+    }
+    /// Returns a mutable reference to the value of the current entry, or `None` if the cursor is at
+    /// the ghost position. If the value is set to the *common* value, the entry is removed when
+    /// the cursor moves off it or is dropped.
+    pub fn value_mut(&mut self) -> Option<&mut V> {
+        self.current.as_ref().and_then(|key| self.map.get_mut(key))
+    }
+
+    /// Moves the cursor to the next *uncommon* entry in key order. If the cursor is at the last
+    /// entry, it moves to the ghost position; if it is at the ghost position, it moves to the first
+    /// entry.
+    pub fn move_next(&mut self) {
+        let next = match &self.current {
+            Some(key) => self.map.range((Bound::Excluded(key), Bound::Unbounded)).next(),
+            None => self.map.first_key_value(),
+        };
+        let next = next.map(|(key, _)| key.clone());
+        self.restore_invariant();
+        self.current = next;
+    }
+    /// Moves the cursor to the previous *uncommon* entry in key order. If the cursor is at the
+    /// first entry, it moves to the ghost position; if it is at the ghost position, it moves to the
+    /// last entry.
+    pub fn move_prev(&mut self) {
+        let prev = match &self.current {
+            Some(key) => self.map.range((Bound::Unbounded, Bound::Excluded(key))).next_back(),
+            None => self.map.last_key_value(),
+        };
+        let prev = prev.map(|(key, _)| key.clone());
+        self.restore_invariant();
+        self.current = prev;
+    }
+
+    /// Removes the current entry from the map and returns it, resetting its key to the *common*
+    /// value. The cursor moves to the next entry (or to the ghost position, if the removed entry
+    /// was the last). Returns `None` if the cursor is at the ghost position.
+    pub fn remove_current(&mut self) -> Option<(K, V)> {
+        let key = self.current.take()?;
+        self.current = self
+            .map
+            .range((Bound::Excluded(&key), Bound::Unbounded))
+            .next()
+            .map(|(k, _)| k.clone());
+        self.map.remove_entry(&key)
+    }
+
+    fn restore_invariant(&mut self) {
+        if let Some(key) = &self.current {
+            if self.map.get(key).is_some_and(C::is_common) {
+                self.map.remove(key);
+            }
+        }
+    }
+}
+
+impl<K: Ord + Clone, V, C: Commonality<V>> Drop for CursorMut<'_, K, V, C> {
+    fn drop(&mut self) {
+        self.restore_invariant();
+    }
+}
+
+impl<K: Ord + Clone + Debug, V: Debug, C: Commonality<V>> Debug for CursorMut<'_, K, V, C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut f = f.debug_tuple("CursorMut");
+        if let Some(key) = &self.current {
+            f.field(key).field(&self.map[key]);
+        }
+        f.finish()
+    }
+}
+
+/// A streaming iterator over the *uncommon* entries of a [TotalBTreeMap] whose keys lie within a
+/// range, which allows their values to be modified.
+///
+/// This is not an [Iterator]: each entry yielded by [`next()`](Self::next) borrows the iterator,
+/// so it must be released before the next entry is requested. This lets the iterator check each
+/// value after it has been modified. When the iterator moves past an entry or is dropped, the
+/// entry is removed if its value was set to the *common* value. Keys within the range that are
+/// associated with the *common* value are not yielded.
+///
+/// Like [CursorMut], the iterator keeps a clone of the current entry's key, and each step looks up
+/// the next entry in the map, taking logarithmic time.
+///
+/// This iterator is created by [TotalBTreeMap::range_mut].
+pub struct RangeMut<'a, K: Ord + Clone, V, C: Commonality<V>, R: RangeBounds<K>> {
+    map: &'a mut BTreeMap<K, V>,
+    range: R,
+    current: Option<K>,
+    finished: bool,
+    _commonality: PhantomPtr<C>,
+}
+
+impl<K: Ord + Clone, V, C: Commonality<V>, R: RangeBounds<K>> RangeMut<'_, K, V, C, R> {
+    /// Advances to the next *uncommon* entry in the range, and returns its key and a mutable
+    /// reference to its value, or `None` if there are no more entries.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<(&K, &mut V)> {
+        if self.finished {
+            return None;
+        }
+        let start = match &self.current {
+            Some(key) => Bound::Excluded(key),
+            None => self.range.start_bound(),
+        };
+        let next = self.map.range((start, self.range.end_bound())).next();
+        let next = next.map(|(key, _)| key.clone());
+        self.restore_invariant();
+        self.current = next;
+        self.finished = self.current.is_none();
+        let key = self.current.as_ref()?;
+        Some((key, self.map.get_mut(key)?))
+    }
+
+    fn restore_invariant(&mut self) {
+        if let Some(key) = &self.current {
+            if self.map.get(key).is_some_and(C::is_common) {
+                self.map.remove(key);
+            }
+        }
+    }
+}
+
+impl<K: Ord + Clone, V, C: Commonality<V>, R: RangeBounds<K>> Drop for RangeMut<'_, K, V, C, R> {
+    fn drop(&mut self) {
+        self.restore_invariant();
+    }
+}
+
+impl<K, V, C, R> Debug for RangeMut<'_, K, V, C, R>
+where
+    K: Ord + Clone + Debug,
+    V: Debug,
+    C: Commonality<V>,
+    R: RangeBounds<K>,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut f = f.debug_tuple("RangeMut");
+        if let Some(key) = &self.current {
+            f.field(key).field(&self.map[key]);
+        }
+        f.finish()
+    }
+}
+
+// --------------------------------------------------------------------------
+// Sub-range views
+
+impl<K: Ord, V, C> TotalBTreeMap<K, V, C> {
+    /// Returns a read-only view of the part of the map whose keys lie within the given range.
+    ///
+    /// The view behaves like a total map over the sub-domain: keys within the range are associated
+    /// with the same values as in this map, and all keys outside the range are associated with the
+    /// *common* value, which is shared with this map. Only the *uncommon* entries within the range
+    /// are visible to methods like [SubMap::len] and [SubMap::iter].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start of the range is greater than its end, or if the start and end are equal
+    /// and both excluded.
+    pub fn sub_range<R: RangeBounds<K>>(&self, range: R) -> SubMap<'_, K, V, C, R> {
+        check_range(&range);
+        SubMap { map: self, range }
+    }
+
+    /// An iterator over the *uncommon* entries in the map whose keys lie within the given range, in
+    /// sorted order. Keys within the range that are associated with the *common* value are not
+    /// yielded.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start of the range is greater than its end, or if the start and end are equal
+    /// and both excluded.
+    pub fn range<T, R>(&self, range: R) -> Range<'_, K, V>
+    where
+        K: Borrow<T>,
+        T: Ord + ?Sized,
+        R: RangeBounds<T>,
+    {
+        Range(self.inner.range(range))
+    }
+}
+
+/// A read-only view of the entries of a [TotalBTreeMap] whose keys lie within a range. Keys outside
+/// the range are associated with the *common* value.
+///
+/// This view is created by [TotalBTreeMap::sub_range].
+pub struct SubMap<'a, K, V, C, R> {
+    map: &'a TotalBTreeMap<K, V, C>,
+    range: R,
+}
+
+impl<K, V, C, R: Clone> Clone for SubMap<'_, K, V, C, R> {
+    fn clone(&self) -> Self {
+        Self { map: self.map, range: self.range.clone() }
+    }
+}
+
+impl<'a, K: Ord, V, C, R: RangeBounds<K>> SubMap<'a, K, V, C, R> {
+    /// Returns a reference to the value associated with the given key. Keys outside the range are
+    /// associated with the *common* value.
+    pub fn get<Q>(&self, key: &Q) -> &'a V
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        if self.range_contains(key) {
+            self.map.get(key)
+        } else {
+            &self.map.common
+        }
+    }
+    /// Returns true if the given key lies within the range and is associated with an *uncommon*
+    /// value.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.range_contains(key) && self.map.contains_key(key)
+    }
+    // Like RangeBounds::contains, but for a borrowed form of the key
+    fn range_contains<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let after_start = match self.range.start_bound() {
+            Bound::Included(start) => start.borrow() <= key,
+            Bound::Excluded(start) => start.borrow() < key,
+            Bound::Unbounded => true,
+        };
+        let before_end = match self.range.end_bound() {
+            Bound::Included(end) => key <= end.borrow(),
+            Bound::Excluded(end) => key < end.borrow(),
+            Bound::Unbounded => true,
+        };
+        after_start && before_end
+    }
+    /// Returns the number of *uncommon* entries within the range.
+    ///
+    /// This takes time linear in the number of entries within the range.
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+    /// Returns true if there are no *uncommon* entries within the range.
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+    /// An iterator over all *uncommon* entries within the range, in sorted order.
+    pub fn iter(&self) -> Range<'a, K, V> {
+        Range(self.map.inner.range((self.range.start_bound(), self.range.end_bound())))
+    }
+}
+
+impl<K, Q, V, C, R> Index<&Q> for SubMap<'_, K, V, C, R>
+where
+    K: Borrow<Q> + Ord,
+    Q: Ord + ?Sized,
+    R: RangeBounds<K>,
+{
+    type Output = V;
+    fn index(&self, index: &Q) -> &Self::Output {
+        self.get(index)
+    }
+}
+
+impl<'a, K: Ord, V, C, R: RangeBounds<K>> IntoIterator for &SubMap<'a, K, V, C, R> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Range<'a, K, V>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K: Ord + Debug, V: Debug, C, R: RangeBounds<K>> Debug for SubMap<'_, K, V, C, R> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        struct Rest;
+        impl Debug for Rest {
+            fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+                write!(f, "...")
+            }
+        }
+        f.debug_map().entries(self.iter()).entry(&Rest, &self.map.common).finish()
+    }
+}
+
+/// An iterator over the *uncommon* entries of a [TotalBTreeMap] whose keys lie within a range.
+///
+/// This iterator is created by [TotalBTreeMap::range] or [SubMap::iter].
+pub struct Range<'a, K, V>(btree_map::Range<'a, K, V>);
+impl<K, V> Clone for Range<'_, K, V> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+impl<K, V> Default for Range<'_, K, V> {
+    fn default() -> Self {
+        Self(Default::default())
+    }
+}
+impl<'a, K, V> Iterator for Range<'a, K, V> {
+    type Item = (&'a K, &'a V);
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+impl<K, V> DoubleEndedIterator for Range<'_, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back()
+    }
+}
+impl<K, V> FusedIterator for Range<'_, K, V> {}
+impl<K: Debug, V: Debug> Debug for Range<'_, K, V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.clone()).finish()
+    }
+}
+
+fn check_range<K: Ord, R: RangeBounds<K>>(range: &R) {
+    match (range.start_bound(), range.end_bound()) {
+        (Bound::Excluded(start), Bound::Excluded(end)) if start == end => {
+            panic!("range start and end are equal and excluded in TotalBTreeMap")
+        }
+        (
+            Bound::Included(start) | Bound::Excluded(start),
+            Bound::Included(end) | Bound::Excluded(end),
+        ) if start > end => panic!("range start is greater than range end in TotalBTreeMap"),
+        _ => {}
+    }
+}
+
 // --------------------------------------------------------------------------
 // Population from iterators
 
@@ -466,9 +1803,223 @@ impl<K: Ord, V, C: Commonality<V>> FromIterator<(K, V)> for TotalBTreeMap<K, V,
     }
 }
 
+impl<'a, K: Ord + Clone, V: Clone, C: Commonality<V>> Extend<(&'a K, &'a V)>
+    for TotalBTreeMap<K, V, C>
+{
+    fn extend<T: IntoIterator<Item = (&'a K, &'a V)>>(&mut self, iter: T) {
+        self.extend(iter.into_iter().map(|(key, value)| (key.clone(), value.clone())));
+    }
+}
+impl<'a, K: Ord + Clone, V: Clone, C: Commonality<V>> FromIterator<(&'a K, &'a V)>
+    for TotalBTreeMap<K, V, C>
+{
+    fn from_iter<T: IntoIterator<Item = (&'a K, &'a V)>>(iter: T) -> Self {
+        let mut this = Self::default();
+        this.extend(iter);
+        this
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<K: Ord + Send, V: Send, C: Commonality<V>> TotalBTreeMap<K, V, C> {
+    /// Constructs a `TotalBTreeMap` from a vector of entries, sorting the entries in parallel.
+    ///
+    /// If the vector contains several entries with the same key, the last one wins, as if the
+    /// entries had been inserted in order. Entries with *common* values are discarded.
+    pub fn par_from_vec(mut entries: Vec<(K, V)>) -> Self {
+        // As in Self::from_vec; the parallel sort is also stable
+        entries.reverse();
+        entries.par_sort_by(|(lhs, _), (rhs, _)| lhs.cmp(rhs));
+        entries.dedup_by(|(lhs, _), (rhs, _)| lhs == rhs);
+        Self {
+            inner: entries.into_iter().filter(|(_, value)| !C::is_common(value)).collect(),
+            common: C::common(),
+            _commonality: PhantomPtr::default(),
+        }
+    }
+}
+
+impl<K: Ord + Enumerable + Clone, V, C> TotalBTreeMap<K, V, C> {
+    /// An iterator over all keys associated with the *common* value in the map, in the order of
+    /// [Enumerable::VALUES].
+    ///
+    /// These are exactly the values of `K` not produced by [`keys()`](Self::keys).
+    pub fn common_keys(&self) -> impl Iterator<Item = K> + '_ {
+        K::VALUES.iter().filter(|key| !self.contains_key(key)).cloned()
+    }
+}
+
+// --------------------------------------------------------------------------
+// Statistics
+
+impl<K, V: Eq + Hash + Clone, C> TotalBTreeMap<K, V, C> {
+    /// Counts how many *uncommon* entries are associated with each distinct value.
+    ///
+    /// Keys associated with the *common* value are not counted, so the *common* value never appears
+    /// in the result.
+    pub fn value_counts(&self) -> HashMap<V, usize> {
+        let mut counts = HashMap::new();
+        for value in self.values() {
+            *counts.entry(value.clone()).or_default() += 1;
+        }
+        counts
+    }
+    /// Counts, for each distinct value, how many more *uncommon* entries of this map are associated
+    /// with it than *uncommon* entries of `other`. The count is negative if `other` has more.
+    ///
+    /// Keys associated with the *common* value in either map are not counted for that map. Values
+    /// with a difference of zero are omitted from the result.
+    pub fn value_delta(&self, other: &Self) -> HashMap<V, i64> {
+        let mut deltas = HashMap::<V, i64>::new();
+        for value in self.values() {
+            *deltas.entry(value.clone()).or_default() += 1;
+        }
+        for value in other.values() {
+            *deltas.entry(value.clone()).or_default() -= 1;
+        }
+        deltas.retain(|_, delta| *delta != 0);
+        deltas
+    }
+}
+
+// --------------------------------------------------------------------------
+// Transformation
+
+impl<K, V, C> TotalBTreeMap<K, V, C> {
+    /// Returns the *uncommon* entries of the map sorted by value, in ascending order (rather than
+    /// by key). Entries with equal values are returned in key order.
+    pub fn into_sorted_by_value(self) -> Vec<(K, V)>
+    where
+        V: Ord,
+    {
+        self.into_sorted_by_value_by(V::cmp)
+    }
+    /// Returns the *uncommon* entries of the map sorted by value, in descending order. Entries with
+    /// equal values are returned in key order.
+    pub fn into_sorted_by_value_desc(self) -> Vec<(K, V)>
+    where
+        V: Ord,
+    {
+        self.into_sorted_by_value_by(|lhs, rhs| rhs.cmp(lhs))
+    }
+    /// Returns the *uncommon* entries of the map sorted by value using the comparator `compare`.
+    /// Entries with equal values are returned in key order.
+    pub fn into_sorted_by_value_by<F>(self, mut compare: F) -> Vec<(K, V)>
+    where
+        F: FnMut(&V, &V) -> Ordering,
+    {
+        let mut entries = Vec::with_capacity(self.len());
+        entries.extend(self.inner);
+        entries.sort_by(|(_, lhs), (_, rhs)| compare(lhs, rhs));
+        entries
+    }
+}
+
+impl<K, V, C> TotalBTreeMap<K, V, C> {
+    /// Rebuilds the map with each key transformed by `f`, keeping the same values.
+    ///
+    /// If `f` maps several keys to the same new key, the value of the greatest original key is
+    /// kept, since entries are visited in sorted order and later entries overwrite earlier ones.
+    pub fn map_keys<K2: Ord, F: FnMut(K) -> K2>(self, mut f: F) -> TotalBTreeMap<K2, V, C> {
+        let mut inner = BTreeMap::new();
+        for (key, value) in self.inner {
+            inner.insert(f(key), value);
+        }
+        TotalBTreeMap { inner, common: self.common, _commonality: PhantomPtr::default() }
+    }
+}
+
+impl<K: Ord + Clone, V: Ord, C> TotalBTreeMap<K, V, C> {
+    /// Viewing both maps as functions, returns their composition `other ∘ self`, which associates
+    /// each key `k` with `other[self[k]]`. The commonality `E` of the result is chosen by the
+    /// caller, and may differ from those of both maps.
+    ///
+    /// Every key that `self` associates with its *common* value `c` is associated with `other[c]`
+    /// in the composition. The result can only represent this if `other[c]` is *common* under `E`;
+    /// this holds, for example, when `other` associates `c` with its own *common* value and `E` is
+    /// the commonality of `other`. In that case the composition is as sparse as `self` or sparser,
+    /// since entries of `self` whose values `other` maps to a *common* value are dropped. If
+    /// instead `other[c]` is *uncommon* under `E`, the composition would associate almost every key
+    /// with an *uncommon* value, which no total map can store, so this returns `None`.
+    ///
+    /// Only the *uncommon* entries of `self` are visited, with one lookup in `other` each.
+    pub fn compose<W, D, E>(&self, other: &TotalBTreeMap<V, W, D>) -> Option<TotalBTreeMap<K, W, E>>
+    where
+        W: Clone,
+        E: Commonality<W>,
+    {
+        if !E::is_common(other.get(&self.common)) {
+            return None;
+        }
+        Some(TotalBTreeMap {
+            inner: self
+                .iter()
+                .map(|(key, value)| (key, other.get(value)))
+                .filter(|(_, value)| !E::is_common(value))
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+            common: E::common(),
+            _commonality: PhantomPtr::default(),
+        })
+    }
+}
+
 // --------------------------------------------------------------------------
 // Low-level access
 
+impl<K, V, C> TotalBTreeMap<K, V, C> {
+    /// Returns a reference to the underlying [BTreeMap] of a [TotalBTreeMap], which contains only
+    /// the *uncommon* entries.
+    pub fn as_btree_map(&self) -> &BTreeMap<K, V> {
+        &self.inner
+    }
+    /// Unwraps the underlying [BTreeMap] of a [TotalBTreeMap], which contains only the *uncommon*
+    /// entries.
+    pub fn into_btree_map(self) -> BTreeMap<K, V> {
+        self.inner
+    }
+}
+
+impl<K: Ord, V, C: Commonality<V>> TotalBTreeMap<K, V, C> {
+    /// Constructs a map from an underlying [BTreeMap], checking that it contains no *common*
+    /// values.
+    ///
+    /// Returns an error listing the keys of the offending entries, and containing `inner` itself,
+    /// if `inner` contains any *common* values. To discard the *common* values instead, use the
+    /// [From] conversion.
+    pub fn try_from_btree_map(
+        inner: BTreeMap<K, V>,
+    ) -> Result<Self, CommonValuePresent<K, BTreeMap<K, V>>>
+    where
+        K: Clone,
+    {
+        let keys = inner
+            .iter()
+            .filter(|(_, value)| C::is_common(value))
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        if !keys.is_empty() {
+            return Err(CommonValuePresent { keys, map: inner });
+        }
+        Ok(Self { inner, common: C::common(), _commonality: PhantomPtr::default() })
+    }
+}
+
+impl<K: Ord, V, C: Commonality<V>> From<BTreeMap<K, V>> for TotalBTreeMap<K, V, C> {
+    /// Constructs a map from an underlying [BTreeMap], discarding any entries with *common* values.
+    /// The [BTreeMap]'s allocation is reused.
+    fn from(mut inner: BTreeMap<K, V>) -> Self {
+        inner.retain(|_, value| !C::is_common(value));
+        Self { inner, common: C::common(), _commonality: PhantomPtr::default() }
+    }
+}
+
+impl<K, V, C> AsRef<BTreeMap<K, V>> for TotalBTreeMap<K, V, C> {
+    fn as_ref(&self) -> &BTreeMap<K, V> {
+        &self.inner
+    }
+}
+
 impl<K: Ord, V, C: Commonality<V>> TotalBTreeMap<K, V, C> {
     /// Returns a mutable view into the underlying [BTreeMap] of a [TotalBTreeMap], from which
     /// mutating iterators can be obtained by calling [BTreeMap::values_mut] or
@@ -478,11 +2029,44 @@ impl<K: Ord, V, C: Commonality<V>> TotalBTreeMap<K, V, C> {
     /// in the map temporarily. When the returned view is dropped, all *uncommon* entries will be
     /// removed, restoring the invariant of [TotalBTreeMap].
     ///
-    /// You don't need this method if you are only mutating individual entries; use the
-    /// [entry][Self::entry] method instead.
+    /// You don't need this method if you are only mutating individual entries, or mutating values
+    /// in place; use the [entry][Self::entry] or [iter_mut][Self::iter_mut] methods instead.
     pub fn as_btree_map_mut(&mut self) -> AsBTreeMapMut<'_, K, V, C> {
         AsBTreeMapMut { map: &mut self.inner, _commonality: PhantomPtr::default() }
     }
+
+    /// Returns true if the map upholds its invariant that no *common* values are stored.
+    ///
+    /// This can only be false if the invariant was circumvented, for example by leaking the view
+    /// returned by [`as_btree_map_mut()`](Self::as_btree_map_mut) with [std::mem::forget] so that
+    /// its cleanup never ran.
+    pub fn is_canonical(&self) -> bool {
+        !self.inner.values().any(C::is_common)
+    }
+    /// Panics if the map stores any *common* values. Does nothing unless debug assertions are
+    /// enabled.
+    pub fn debug_assert_invariant(&self) {
+        debug_assert!(self.is_canonical(), "TotalBTreeMap stores common values");
+    }
+    /// Removes any stored entries with *common* values, restoring the invariant of [TotalBTreeMap].
+    pub fn repair(&mut self) {
+        self.inner.retain(|_, value| !C::is_common(value));
+    }
+
+    /// Constructs a map directly from an underlying [BTreeMap] and the *common* value, without
+    /// scanning the entries.
+    ///
+    /// The caller must ensure that `inner` contains no *common* values, and that `common` is the
+    /// *common* value. Unlike violating a safety contract, violating this contract can't cause
+    /// undefined behavior, but the map will behave incorrectly (for example, its
+    /// [`len()`](Self::len) will count *common* entries) until it is [repaired](Self::repair).
+    /// With debug assertions enabled, this method panics if the contract is violated.
+    pub fn from_parts(inner: BTreeMap<K, V>, common: V) -> Self {
+        debug_assert!(C::is_common(&common), "TotalBTreeMap::from_parts given an uncommon value");
+        let this = Self { inner, common, _commonality: PhantomPtr::default() };
+        this.debug_assert_invariant();
+        this
+    }
 }
 
 /// A mutable view into the underlying [BTreeMap] of a [TotalBTreeMap].
@@ -586,6 +2170,7 @@ impl<K: Debug, V: Debug, C> Debug for TotalBTreeMap<K, V, C> {
                 write!(f, "...")
             }
         }
-        f.debug_map().entries(self.iter()).entry(&Rest, &self.common).finish()
+        let (common, entries) = self.describe();
+        f.debug_map().entries(entries).entry(&Rest, common).finish()
     }
 }
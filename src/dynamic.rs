@@ -0,0 +1,260 @@
+//! Provides [TotalHashMapDyn], a total hash map whose *common* value is chosen at runtime.
+
+use std::{
+    borrow::Borrow,
+    collections::{
+        hash_map::{self, RandomState},
+        HashMap,
+    },
+    fmt::{self, Debug, Formatter},
+    hash::{BuildHasher, Hash},
+    mem,
+    ops::{Deref, DerefMut, Index},
+};
+
+use crate::hash_map::{Iter, Keys, Values};
+
+// --------------------------------------------------------------------------
+
+/// A hash map in which every possible key has an associated value, and whose *common* value and
+/// predicate for *common* values are chosen when the map is constructed.
+///
+/// This behaves like a [TotalHashMap](crate::TotalHashMap), except that instead of a
+/// [Commonality](crate::Commonality) type parameter, the map stores the *common* value and a boxed
+/// predicate that decides which values are *common*. This suits *common* values that are only known
+/// at runtime, such as a sentinel read from configuration.
+pub struct TotalHashMapDyn<K, V, S = RandomState> {
+    inner: HashMap<K, V, S>,
+    common: V,
+    is_common: Box<dyn Fn(&V) -> bool + Send + Sync>,
+}
+
+impl<K, V> TotalHashMapDyn<K, V> {
+    /// Constructs a `TotalHashMapDyn` in which all keys are associated with `common`, and in which
+    /// a value is *common* if `is_common` returns true for it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `is_common(&common)` is false.
+    pub fn new_with_common(
+        common: V,
+        is_common: impl Fn(&V) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        Self::with_common_and_hasher(common, is_common, RandomState::new())
+    }
+}
+
+impl<K, V, S> TotalHashMapDyn<K, V, S> {
+    /// Constructs a `TotalHashMapDyn` that uses the given hasher to hash keys, in which all keys
+    /// are associated with `common`, and in which a value is *common* if `is_common` returns true
+    /// for it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `is_common(&common)` is false.
+    pub fn with_common_and_hasher(
+        common: V,
+        is_common: impl Fn(&V) -> bool + Send + Sync + 'static,
+        hasher: S,
+    ) -> Self {
+        assert!(is_common(&common), "TotalHashMapDyn given an uncommon value as its common value");
+        Self { inner: HashMap::with_hasher(hasher), common, is_common: Box::new(is_common) }
+    }
+
+    /// Returns the number of *uncommon* entries in the map.
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+    /// Returns true if the map contains no *uncommon* entries.
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+    /// Resets all entries in the map to the *common* value.
+    pub fn clear(&mut self) {
+        self.inner.clear()
+    }
+    /// Returns the *common* value.
+    pub fn common(&self) -> &V {
+        &self.common
+    }
+    /// Returns true if the map's predicate considers `value` *common*.
+    pub fn is_common(&self, value: &V) -> bool {
+        (self.is_common)(value)
+    }
+}
+
+// --------------------------------------------------------------------------
+// Element access
+
+impl<K: Eq + Hash, V, S: BuildHasher> TotalHashMapDyn<K, V, S> {
+    /// Returns a reference to the value associated with the given key.
+    pub fn get<Q>(&self, key: &Q) -> &V
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.inner.get(key).unwrap_or(&self.common)
+    }
+    /// Returns true if the map contains an *uncommon* entry with the given key.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.inner.contains_key(key)
+    }
+
+    /// Associates a key with a value in the map, and returns the value previously associated with
+    /// that key.
+    pub fn insert(&mut self, key: K, value: V) -> V
+    where
+        V: Clone,
+    {
+        if self.is_common(&value) {
+            self.remove(&key)
+        } else {
+            self.inner.insert(key, value).unwrap_or_else(|| self.common.clone())
+        }
+    }
+    /// Associates a key with the *common* value in the map, and returns the value previously
+    /// associated with that key.
+    pub fn remove<Q>(&mut self, key: &Q) -> V
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+        V: Clone,
+    {
+        self.inner.remove(key).unwrap_or_else(|| self.common.clone())
+    }
+
+    /// Gets the given key's associated entry in the map for in-place manipulation.
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V>
+    where
+        V: Clone,
+    {
+        Entry {
+            inner: match self.inner.entry(key) {
+                hash_map::Entry::Occupied(inner) => EntryInner::Occupied { inner },
+                hash_map::Entry::Vacant(inner) => {
+                    EntryInner::Vacant { inner, value: self.common.clone() }
+                }
+            },
+            is_common: &*self.is_common,
+        }
+    }
+}
+
+impl<K: Eq + Hash + Borrow<Q>, Q: Eq + Hash + ?Sized, V, S: BuildHasher> Index<&Q>
+    for TotalHashMapDyn<K, V, S>
+{
+    type Output = V;
+    fn index(&self, index: &Q) -> &Self::Output {
+        self.get(index)
+    }
+}
+
+/// A view into a single entry in a [TotalHashMapDyn].
+///
+/// The view dereferences to the entry's value, which may be changed freely. When the view is
+/// dropped, the entry is removed from the map if the map's predicate considers its value *common*,
+/// or stored otherwise.
+///
+/// This view is constructed from [TotalHashMapDyn::entry].
+pub struct Entry<'a, K, V> {
+    inner: EntryInner<'a, K, V>,
+    is_common: &'a (dyn Fn(&V) -> bool + Send + Sync),
+}
+
+enum EntryInner<'a, K, V> {
+    Occupied { inner: hash_map::OccupiedEntry<'a, K, V> },
+    Vacant { inner: hash_map::VacantEntry<'a, K, V>, value: V },
+    Dropping,
+}
+
+impl<K, V> Deref for Entry<'_, K, V> {
+    type Target = V;
+    fn deref(&self) -> &Self::Target {
+        match &self.inner {
+            EntryInner::Occupied { inner } => inner.get(),
+            EntryInner::Vacant { value, .. } => value,
+            EntryInner::Dropping => unreachable!(),
+        }
+    }
+}
+impl<K, V> DerefMut for Entry<'_, K, V> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        match &mut self.inner {
+            EntryInner::Occupied { inner } => inner.get_mut(),
+            EntryInner::Vacant { value, .. } => value,
+            EntryInner::Dropping => unreachable!(),
+        }
+    }
+}
+
+impl<K, V> Drop for Entry<'_, K, V> {
+    fn drop(&mut self) {
+        match mem::replace(&mut self.inner, EntryInner::Dropping) {
+            EntryInner::Occupied { inner } => {
+                if (self.is_common)(inner.get()) {
+                    inner.remove();
+                }
+            }
+            EntryInner::Vacant { inner, value } => {
+                if !(self.is_common)(&value) {
+                    inner.insert(value);
+                }
+            }
+            EntryInner::Dropping => unreachable!(),
+        }
+    }
+}
+
+impl<K: Debug, V: Debug> Debug for Entry<'_, K, V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut f = f.debug_tuple("Entry");
+        match &self.inner {
+            EntryInner::Occupied { inner } => f.field(inner.key()).field(inner.get()),
+            EntryInner::Vacant { inner, value } => f.field(inner.key()).field(value),
+            EntryInner::Dropping => &mut f,
+        };
+        f.finish()
+    }
+}
+
+// --------------------------------------------------------------------------
+// Iteration
+
+impl<K, V, S> TotalHashMapDyn<K, V, S> {
+    /// An iterator over all keys associated with *uncommon* values in the map, in arbitrary order.
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys(self.inner.keys())
+    }
+    /// An iterator over all *uncommon* values in the map, in arbitrary order.
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values(self.inner.values())
+    }
+    /// An iterator over all *uncommon* entries in the map, in arbitrary order.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter(self.inner.iter())
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a TotalHashMapDyn<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+// --------------------------------------------------------------------------
+// Miscellaneous traits
+
+impl<K: Debug, V: Debug, S> Debug for TotalHashMapDyn<K, V, S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TotalHashMapDyn")
+            .field("common", &self.common)
+            .field("uncommon", &self.inner)
+            .finish()
+    }
+}
@@ -0,0 +1,80 @@
+//! Provides [EmptyCommonality], a [commonality](Commonality) for collections under which the empty
+//! collection is the *common* value.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet, LinkedList, VecDeque},
+    hash::BuildHasher,
+};
+
+use crate::Commonality;
+
+/// A [commonality](Commonality) for collections, based on their `is_empty` methods.
+///
+/// A [TotalHashMap](crate::TotalHashMap) or [TotalBTreeMap](crate::TotalBTreeMap) using this
+/// commonality only stores entries with non-empty collections as values.
+///
+/// For most collections this behaves the same as [DefaultCommonality](crate::DefaultCommonality),
+/// but it doesn't require the collection's elements to implement [PartialEq]. For example, it
+/// supports `Vec<T>` for any `T`. Both commonalities check whether a value is *common* in constant
+/// time, since comparing a collection with an empty one only compares their lengths; the
+/// `commonality` benchmark shows no measurable difference in the cost of pruning entries.
+pub struct EmptyCommonality(());
+
+macro_rules! impl_empty_commonality {
+    ($($Collection:ident<$($T:ident),*>),* $(,)?) => {
+        $(
+            impl<$($T),*> Commonality<$Collection<$($T),*>> for EmptyCommonality {
+                fn common() -> $Collection<$($T),*> {
+                    $Collection::new()
+                }
+                fn is_common(value: &$Collection<$($T),*>) -> bool {
+                    value.is_empty()
+                }
+            }
+        )*
+    };
+}
+
+impl_empty_commonality!(
+    Vec<T>,
+    VecDeque<T>,
+    LinkedList<T>,
+    BTreeMap<K, V>,
+    BTreeSet<T>,
+);
+
+impl Commonality<String> for EmptyCommonality {
+    fn common() -> String {
+        String::new()
+    }
+    fn is_common(value: &String) -> bool {
+        value.is_empty()
+    }
+}
+
+impl<T: Ord> Commonality<BinaryHeap<T>> for EmptyCommonality {
+    fn common() -> BinaryHeap<T> {
+        BinaryHeap::new()
+    }
+    fn is_common(value: &BinaryHeap<T>) -> bool {
+        value.is_empty()
+    }
+}
+
+impl<K, V, S: BuildHasher + Default> Commonality<HashMap<K, V, S>> for EmptyCommonality {
+    fn common() -> HashMap<K, V, S> {
+        HashMap::default()
+    }
+    fn is_common(value: &HashMap<K, V, S>) -> bool {
+        value.is_empty()
+    }
+}
+
+impl<T, S: BuildHasher + Default> Commonality<HashSet<T, S>> for EmptyCommonality {
+    fn common() -> HashSet<T, S> {
+        HashSet::default()
+    }
+    fn is_common(value: &HashSet<T, S>) -> bool {
+        value.is_empty()
+    }
+}
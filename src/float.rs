@@ -0,0 +1,88 @@
+//! Provides [commonalities](Commonality) for floating-point values.
+
+use crate::{Commonality, ConstCommonality, PhantomPtr};
+
+/// Provides the threshold of a [ThresholdCommonality].
+///
+/// Const generic parameters can't be floats, so the threshold is given by implementing this trait
+/// for a marker type instead.
+pub trait Threshold {
+    /// The largest magnitude of a *common* value. This must not be negative.
+    const EPSILON: f64;
+}
+
+/// A [commonality](Commonality) for `f32` and `f64`, under which zero is the *common* value, and
+/// every value whose magnitude is at most `T::EPSILON` is also *common*.
+///
+/// A [TotalHashMap](crate::TotalHashMap) or [TotalBTreeMap](crate::TotalBTreeMap) using this
+/// commonality discards near-zero values on insertion, and presents them as exactly zero. NaN is
+/// never *common*.
+///
+/// ```
+/// use total_maps::{float::Threshold, ThresholdCommonality, TotalHashMap};
+///
+/// struct NoiseFloor;
+/// impl Threshold for NoiseFloor {
+///     const EPSILON: f64 = 1e-6;
+/// }
+///
+/// let mut signal = TotalHashMap::<u32, f64, ThresholdCommonality<NoiseFloor>>::new();
+/// signal.insert(0, 0.5);
+/// signal.insert(1, 1e-9);
+/// signal.insert(2, -1e-7);
+/// assert_eq!(signal.len(), 1);
+/// assert_eq!(signal[&1], 0.0);
+/// ```
+pub struct ThresholdCommonality<T>(PhantomPtr<T>);
+
+macro_rules! impl_threshold_commonality {
+    ($($t:ty)*) => {$(
+        impl<T: Threshold> Commonality<$t> for ThresholdCommonality<T> {
+            const CHEAP: bool = true;
+
+            fn common() -> $t {
+                0.0
+            }
+            fn is_common(value: &$t) -> bool {
+                value.abs() <= T::EPSILON as $t
+            }
+        }
+
+        impl<T: Threshold> ConstCommonality<$t> for ThresholdCommonality<T> {
+            const COMMON: $t = 0.0;
+        }
+    )*};
+}
+impl_threshold_commonality!(f32 f64);
+
+/// A [commonality](Commonality) for `f32` and `f64`, under which NaN is the *common* value.
+///
+/// This suits maps in which NaN represents an unknown or missing value: a
+/// [TotalHashMap](crate::TotalHashMap) or [TotalBTreeMap](crate::TotalBTreeMap) using this
+/// commonality only stores entries with non-NaN values.
+///
+/// *Note:* NaN isn't equal to itself, so neither is a map using this commonality. Unless every
+/// possible key is associated with a non-NaN value (which is only possible when the key type has
+/// finitely many values), some key is associated with NaN, so `map == map` is false, and
+/// `map.partial_cmp(&map)` is [None].
+pub struct NaNCommonality(());
+
+macro_rules! impl_nan_commonality {
+    ($($t:ident)*) => {$(
+        impl Commonality<$t> for NaNCommonality {
+            const CHEAP: bool = true;
+
+            fn common() -> $t {
+                $t::NAN
+            }
+            fn is_common(value: &$t) -> bool {
+                value.is_nan()
+            }
+        }
+
+        impl ConstCommonality<$t> for NaNCommonality {
+            const COMMON: $t = $t::NAN;
+        }
+    )*};
+}
+impl_nan_commonality!(f32 f64);
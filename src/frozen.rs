@@ -0,0 +1,218 @@
+//! Provides [FrozenTotalMap], an immutable map in which every possible key has an associated value.
+//! Only entries with *uncommon* values are actually stored in the map; all other keys are presumed
+//! to be associated with a *common* value.
+
+use std::{
+    borrow::Borrow,
+    fmt::{self, Debug, Formatter},
+    hash::Hash,
+    iter::FusedIterator,
+    ops::Index,
+    slice,
+};
+
+use crate::{Commonality, DefaultCommonality, PhantomPtr, TotalBTreeMap, TotalHashMap};
+
+// --------------------------------------------------------------------------
+
+/// An immutable map in which every possible key has an associated value. Only entries with
+/// *uncommon* values are actually stored in the map; all other keys are presumed to be associated
+/// with a *common* value.
+///
+/// See the [crate documentation](crate) for more information.
+///
+/// The *uncommon* entries are stored in a sorted slice and looked up by binary search. This is more
+/// compact and cache-friendly than a [TotalBTreeMap], which makes it a good fit for data that is
+/// loaded once and then queried many times. A `FrozenTotalMap` is created by
+/// [TotalBTreeMap::freeze] or [TotalHashMap::freeze], and can be converted back into a
+/// [TotalBTreeMap] by [thaw](Self::thaw).
+pub struct FrozenTotalMap<K, V, C = DefaultCommonality> {
+    entries: Box<[(K, V)]>, // sorted by key, with no duplicate keys and no common values
+    common: V,
+    _commonality: PhantomPtr<C>,
+}
+
+impl<K: Clone, V: Clone, C> Clone for FrozenTotalMap<K, V, C> {
+    fn clone(&self) -> Self {
+        Self {
+            entries: self.entries.clone(),
+            common: self.common.clone(),
+            _commonality: PhantomPtr::default(),
+        }
+    }
+}
+
+impl<K, V, C: Commonality<V>> Default for FrozenTotalMap<K, V, C> {
+    fn default() -> Self {
+        Self { entries: Box::default(), common: C::common(), _commonality: PhantomPtr::default() }
+    }
+}
+
+impl<K, V, C> FrozenTotalMap<K, V, C> {
+    /// Returns the number of *uncommon* entries in the map.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    /// Returns true if the map contains no *uncommon* entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+// --------------------------------------------------------------------------
+// Freezing and thawing
+
+impl<K: Ord, V, C> TotalBTreeMap<K, V, C> {
+    /// Converts this map into an immutable [FrozenTotalMap] with the same entries.
+    pub fn freeze(self) -> FrozenTotalMap<K, V, C> {
+        FrozenTotalMap {
+            entries: self.inner.into_iter().collect(),
+            common: self.common,
+            _commonality: PhantomPtr::default(),
+        }
+    }
+}
+
+impl<K: Ord + Hash, V, C, S> TotalHashMap<K, V, C, S> {
+    /// Converts this map into an immutable [FrozenTotalMap] with the same entries.
+    pub fn freeze(self) -> FrozenTotalMap<K, V, C> {
+        let mut entries = self.inner.into_iter().collect::<Box<[_]>>();
+        entries.sort_unstable_by(|(lhs, _), (rhs, _)| lhs.cmp(rhs));
+        FrozenTotalMap { entries, common: self.common, _commonality: PhantomPtr::default() }
+    }
+}
+
+impl<K: Ord, V, C> FrozenTotalMap<K, V, C> {
+    /// Converts this map back into a mutable [TotalBTreeMap] with the same entries.
+    pub fn thaw(self) -> TotalBTreeMap<K, V, C> {
+        TotalBTreeMap {
+            inner: self.entries.into_vec().into_iter().collect(),
+            common: self.common,
+            _commonality: PhantomPtr::default(),
+        }
+    }
+}
+
+// --------------------------------------------------------------------------
+// Element access
+
+impl<K: Ord, V, C> FrozenTotalMap<K, V, C> {
+    /// Returns a reference to the value associated with the given key.
+    pub fn get<Q>(&self, key: &Q) -> &V
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        match self.search(key) {
+            Ok(index) => &self.entries[index].1,
+            Err(_) => &self.common,
+        }
+    }
+    /// Returns true if the map contains an *uncommon* entry with the given key.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.search(key).is_ok()
+    }
+
+    fn search<Q>(&self, key: &Q) -> Result<usize, usize>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.entries.binary_search_by(|(probe, _)| probe.borrow().cmp(key))
+    }
+}
+
+impl<K, Q, V, C> Index<&Q> for FrozenTotalMap<K, V, C>
+where
+    K: Ord + Borrow<Q>,
+    Q: Ord + ?Sized,
+{
+    type Output = V;
+    fn index(&self, index: &Q) -> &Self::Output {
+        self.get(index)
+    }
+}
+
+// --------------------------------------------------------------------------
+// Iteration
+
+impl<K, V, C> FrozenTotalMap<K, V, C> {
+    /// An iterator over all *uncommon* entries in the map, in sorted order.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter(self.entries.iter())
+    }
+}
+
+impl<'a, K, V, C> IntoIterator for &'a FrozenTotalMap<K, V, C> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the *uncommon* entries in a [FrozenTotalMap].
+///
+/// This iterator is created by [FrozenTotalMap::iter].
+pub struct Iter<'a, K, V>(slice::Iter<'a, (K, V)>);
+impl<K, V> Clone for Iter<'_, K, V> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+impl<K, V> Default for Iter<'_, K, V> {
+    fn default() -> Self {
+        Self(Default::default())
+    }
+}
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(key, value)| (key, value))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+impl<K, V> DoubleEndedIterator for Iter<'_, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back().map(|(key, value)| (key, value))
+    }
+}
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+impl<K, V> FusedIterator for Iter<'_, K, V> {}
+impl<K: Debug, V: Debug> Debug for Iter<'_, K, V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.clone()).finish()
+    }
+}
+
+// --------------------------------------------------------------------------
+// Miscellaneous traits
+
+impl<K: PartialEq, V: PartialEq, C> PartialEq for FrozenTotalMap<K, V, C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.common == other.common && self.entries == other.entries
+    }
+}
+impl<K: Eq, V: Eq, C> Eq for FrozenTotalMap<K, V, C> {}
+
+impl<K: Debug, V: Debug, C> Debug for FrozenTotalMap<K, V, C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        struct Rest;
+        impl Debug for Rest {
+            fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+                write!(f, "...")
+            }
+        }
+        f.debug_map().entries(self.iter()).entry(&Rest, &self.common).finish()
+    }
+}
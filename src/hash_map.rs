@@ -3,16 +3,28 @@
 //! associated with a *common* value.
 
 use std::{
-    borrow::Borrow,
-    collections::{hash_map, HashMap},
-    fmt::{self, Debug, Formatter},
-    hash::Hash,
+    borrow::{Borrow, Cow},
+    cmp::Ordering,
+    collections::{
+        hash_map::{self, DefaultHasher, RandomState},
+        HashMap, TryReserveError,
+    },
+    error::Error,
+    fmt::{self, Debug, Display, Formatter},
+    hash::{BuildHasher, Hash, Hasher},
     iter::FusedIterator,
     mem,
-    ops::{Deref, DerefMut, Index},
+    ops::{AddAssign, Deref, DerefMut, Index},
 };
 
-use crate::{Commonality, DefaultCommonality, PhantomPtr};
+#[cfg(feature = "num-traits")]
+use num_traits::{CheckedSub, One};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::{
+    CommonValuePresent, Commonality, DefaultCommonality, Enumerable, Occupancy, PhantomPtr,
+};
 
 // --------------------------------------------------------------------------
 
@@ -25,13 +37,17 @@ use crate::{Commonality, DefaultCommonality, PhantomPtr};
 /// The API more-or-less matches that of [HashMap]. However, methods that treat this type like a
 /// collection (for example, [`len()`](Self::len) and [`iter()`](Self::iter)) operate only on the
 /// *uncommon* entries.
-pub struct TotalHashMap<K, V, C = DefaultCommonality> {
-    inner: HashMap<K, V>,
-    common: V, // need to store this value so we can return references to it, e.g., in Self::get
-    _commonality: PhantomPtr<C>,
+///
+/// Like [HashMap], the map uses [RandomState] to hash keys by default; a different [BuildHasher]
+/// can be selected with the `S` type parameter.
+pub struct TotalHashMap<K, V, C = DefaultCommonality, S = RandomState> {
+    pub(crate) inner: HashMap<K, V, S>,
+    // need to store this value so we can return references to it, e.g., in Self::get
+    pub(crate) common: V,
+    pub(crate) _commonality: PhantomPtr<C>,
 }
 
-impl<K: Clone, V: Clone, C> Clone for TotalHashMap<K, V, C> {
+impl<K: Clone, V: Clone, C, S: Clone> Clone for TotalHashMap<K, V, C, S> {
     fn clone(&self) -> Self {
         Self {
             inner: self.inner.clone(),
@@ -41,7 +57,7 @@ impl<K: Clone, V: Clone, C> Clone for TotalHashMap<K, V, C> {
     }
 }
 
-impl<K, V, C: Commonality<V>> Default for TotalHashMap<K, V, C> {
+impl<K, V, C: Commonality<V>, S: Default> Default for TotalHashMap<K, V, C, S> {
     fn default() -> Self {
         Self { inner: HashMap::default(), common: C::common(), _commonality: PhantomPtr::default() }
     }
@@ -51,9 +67,102 @@ impl<K, V, C: Commonality<V>> TotalHashMap<K, V, C> {
     pub fn new() -> Self {
         Self::default()
     }
+    /// Constructs a `TotalHashMap` in which all keys are associated with the *common* value, with
+    /// room for at least `capacity` *uncommon* entries before reallocating.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            inner: HashMap::with_capacity(capacity),
+            common: C::common(),
+            _commonality: PhantomPtr::default(),
+        }
+    }
+}
+
+impl<K, V, C: Commonality<V>, S> TotalHashMap<K, V, C, S> {
+    /// Constructs a `TotalHashMap` that uses the given hasher to hash keys, in which all keys are
+    /// associated with the *common* value.
+    pub fn with_hasher(hasher: S) -> Self {
+        Self::with_capacity_and_hasher(0, hasher)
+    }
+    /// Constructs a `TotalHashMap` that uses the given hasher to hash keys, in which all keys are
+    /// associated with the *common* value, with room for at least `capacity` *uncommon* entries
+    /// before reallocating.
+    pub fn with_capacity_and_hasher(capacity: usize, hasher: S) -> Self {
+        Self {
+            inner: HashMap::with_capacity_and_hasher(capacity, hasher),
+            common: C::common(),
+            _commonality: PhantomPtr::default(),
+        }
+    }
+}
+
+impl<K, V> TotalHashMap<K, V> {
+    /// Returns a [Builder] for configuring the capacity, commonality, and hasher of a new
+    /// `TotalHashMap`.
+    pub fn builder() -> Builder<K, V> {
+        Builder {
+            capacity: 0,
+            hasher: RandomState::new(),
+            _entries: PhantomPtr::default(),
+            _commonality: PhantomPtr::default(),
+        }
+    }
+}
+
+/// A builder for a [TotalHashMap], which allows its capacity, [commonality](Commonality), and
+/// hasher to be configured without naming every type parameter up front.
+///
+/// This builder is created by [TotalHashMap::builder].
+pub struct Builder<K, V, C = DefaultCommonality, S = RandomState> {
+    capacity: usize,
+    hasher: S,
+    _entries: PhantomPtr<(K, V)>,
+    _commonality: PhantomPtr<C>,
+}
+
+impl<K, V, C, S> Builder<K, V, C, S> {
+    /// Sets the number of *uncommon* entries the map can hold before reallocating.
+    pub fn capacity(self, capacity: usize) -> Self {
+        Self { capacity, ..self }
+    }
+    /// Sets the [commonality](Commonality) of the map.
+    pub fn commonality<C2: Commonality<V>>(self) -> Builder<K, V, C2, S> {
+        Builder {
+            capacity: self.capacity,
+            hasher: self.hasher,
+            _entries: PhantomPtr::default(),
+            _commonality: PhantomPtr::default(),
+        }
+    }
+    /// Sets the hasher used by the map to hash keys.
+    pub fn hasher<S2: BuildHasher>(self, hasher: S2) -> Builder<K, V, C, S2> {
+        Builder {
+            capacity: self.capacity,
+            hasher,
+            _entries: PhantomPtr::default(),
+            _commonality: PhantomPtr::default(),
+        }
+    }
+}
+
+impl<K, V, C: Commonality<V>, S> Builder<K, V, C, S> {
+    /// Constructs a [TotalHashMap] with the configured parameters, in which all keys are associated
+    /// with the *common* value.
+    pub fn build(self) -> TotalHashMap<K, V, C, S> {
+        TotalHashMap::with_capacity_and_hasher(self.capacity, self.hasher)
+    }
 }
 
-impl<K, V, C> TotalHashMap<K, V, C> {
+impl<K, V, C, S: Debug> Debug for Builder<K, V, C, S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Builder")
+            .field("capacity", &self.capacity)
+            .field("hasher", &self.hasher)
+            .finish()
+    }
+}
+
+impl<K, V, C, S> TotalHashMap<K, V, C, S> {
     /// Returns the number of *uncommon* entries in the map.
     pub fn len(&self) -> usize {
         self.inner.len()
@@ -66,12 +175,82 @@ impl<K, V, C> TotalHashMap<K, V, C> {
     pub fn clear(&mut self) {
         self.inner.clear()
     }
+    /// Returns a reference to the map's hasher.
+    pub fn hasher(&self) -> &S {
+        self.inner.hasher()
+    }
+
+    /// Returns the number of *uncommon* entries in the map. This is the same as
+    /// [`len()`](Self::len), spelled out for readers who might expect `len` to count every key.
+    ///
+    /// Every possible key has a value in a total map, so the logical size of its domain is the
+    /// number of values of `K`, which is usually unbounded. Neither method reports that.
+    pub fn effective_len(&self) -> usize {
+        self.len()
+    }
+    /// Returns true if every key is associated with the *common* value. This is the same as
+    /// [`is_empty()`](Self::is_empty).
+    pub fn is_trivial(&self) -> bool {
+        self.is_empty()
+    }
+    /// Returns a short human-readable description of the map, such as
+    /// `"3 uncommon entries, common = 0"`.
+    pub fn summary(&self) -> String
+    where
+        V: Debug,
+    {
+        let plural = if self.len() == 1 { "entry" } else { "entries" };
+        format!("{} uncommon {plural}, common = {:?}", self.len(), self.common)
+    }
+}
+
+// --------------------------------------------------------------------------
+// Capacity management
+
+impl<K, V, C, S> TotalHashMap<K, V, C, S> {
+    /// Returns the number of *uncommon* entries the map can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity()
+    }
+    /// Returns the ratio of [`len()`](Self::len) to [`capacity()`](Self::capacity), or zero if the
+    /// map has no capacity. A low load factor suggests that
+    /// [`shrink_to_fit()`](Self::shrink_to_fit) could reclaim memory.
+    pub fn load_factor(&self) -> f64 {
+        match self.capacity() {
+            0 => 0.0,
+            capacity => self.len() as f64 / capacity as f64,
+        }
+    }
+    /// Returns a rough estimate of the number of bytes used by the map: the size of the map itself
+    /// plus the size of one entry for each unit of [`capacity()`](Self::capacity).
+    ///
+    /// The estimate does not include heap memory owned by the keys and values, nor the hash table's
+    /// internal bookkeeping.
+    pub fn memory_hint(&self) -> usize {
+        mem::size_of::<Self>() + self.capacity() * mem::size_of::<(K, V)>()
+    }
+}
+
+impl<K: Eq + Hash, V, C, S: BuildHasher> TotalHashMap<K, V, C, S> {
+    /// Reserves capacity for at least `additional` more *uncommon* entries.
+    pub fn reserve(&mut self, additional: usize) {
+        self.inner.reserve(additional)
+    }
+    /// Tries to reserve capacity for at least `additional` more *uncommon* entries, returning an
+    /// error instead of panicking or aborting if the capacity overflows or allocation fails.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.inner.try_reserve(additional)
+    }
+    /// Shrinks the capacity of the map as much as possible.
+    pub fn shrink_to_fit(&mut self) {
+        self.inner.shrink_to_fit()
+    }
 }
 
 // --------------------------------------------------------------------------
 // Element access
 
-impl<K: Eq + Hash, V, C> TotalHashMap<K, V, C> {
+impl<K: Eq + Hash, V, C, S: BuildHasher> TotalHashMap<K, V, C, S> {
     /// Returns a reference to the value associated with the given key.
     pub fn get<Q>(&self, key: &Q) -> &V
     where
@@ -80,6 +259,28 @@ impl<K: Eq + Hash, V, C> TotalHashMap<K, V, C> {
     {
         self.inner.get(key).unwrap_or(&self.common)
     }
+    /// Returns the stored key and value of the given key's entry, if the key is associated with an
+    /// *uncommon* value. Keys associated with the *common* value have no stored key, so this
+    /// returns `None` for them.
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.inner.get_key_value(key)
+    }
+    /// Returns the value associated with the given key, borrowed from the map where possible.
+    ///
+    /// Unlike [get](Self::get), this allows the *common* value to be returned as an owned value, so
+    /// callers using it will be unaffected if the map stops storing its *common* value.
+    pub fn get_cow<Q>(&self, key: &Q) -> Cow<'_, V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+        V: Clone,
+    {
+        Cow::Borrowed(self.get(key))
+    }
     /// Returns true if the map contains an *uncommon* entry with the given key.
     pub fn contains_key<Q>(&self, key: &Q) -> bool
     where
@@ -88,16 +289,53 @@ impl<K: Eq + Hash, V, C> TotalHashMap<K, V, C> {
     {
         self.inner.contains_key(key)
     }
+    /// Returns whether the given key is associated with an *uncommon* value stored in the map, or
+    /// with the implicit *common* value.
+    pub fn raw_occupancy<Q>(&self, key: &Q) -> Occupancy
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        if self.contains_key(key) {
+            Occupancy::UncommonStored
+        } else {
+            Occupancy::CommonImplicit
+        }
+    }
+    /// Returns references to the values associated with each of the given keys, in the same order.
+    pub fn get_all<'a, Q>(&'a self, keys: &[&Q]) -> Vec<&'a V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        keys.iter().map(|key| self.get(key)).collect()
+    }
+    /// Hashes the given key using the map's hasher.
+    ///
+    /// Maps whose hashers produce the same hashes (for example, a map and its clone) hash a key
+    /// identically, so this can be used to route keys consistently across shards.
+    pub fn hash_of<Q>(&self, key: &Q) -> u64
+    where
+        K: Borrow<Q>,
+        Q: Hash + ?Sized,
+    {
+        self.inner.hasher().hash_one(key)
+    }
 }
 
-impl<K: Eq + Hash + Borrow<Q>, Q: Eq + Hash + ?Sized, V, C> Index<&Q> for TotalHashMap<K, V, C> {
+impl<K, Q, V, C, S> Index<&Q> for TotalHashMap<K, V, C, S>
+where
+    K: Eq + Hash + Borrow<Q>,
+    Q: Eq + Hash + ?Sized,
+    S: BuildHasher,
+{
     type Output = V;
     fn index(&self, index: &Q) -> &Self::Output {
         self.get(index)
     }
 }
 
-impl<K: Eq + Hash, V, C: Commonality<V>> TotalHashMap<K, V, C> {
+impl<K: Eq + Hash, V, C: Commonality<V>, S: BuildHasher> TotalHashMap<K, V, C, S> {
     /// Associates a key with a value in the map, and returns the value previously associated with
     /// that key.
     pub fn insert(&mut self, key: K, value: V) -> V {
@@ -115,21 +353,206 @@ impl<K: Eq + Hash, V, C: Commonality<V>> TotalHashMap<K, V, C> {
         self.inner.remove(key).unwrap_or_else(C::common)
     }
 
+    /// Associates a key with the *common* value in the map, and returns the stored key and the
+    /// value previously associated with it if that value was *uncommon*, or `None` if it was
+    /// *common*.
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.inner.remove_entry(key)
+    }
+
+    /// Moves all *uncommon* entries from `other` into this map, leaving `other` with no *uncommon*
+    /// entries. If a key has an *uncommon* value in both maps, the value from `other` wins, as if
+    /// each of its entries had been inserted into this map.
+    pub fn append(&mut self, other: &mut Self) {
+        self.inner.extend(other.inner.drain())
+    }
+
+    /// Resets an arbitrary *uncommon* entry to the *common* value, and returns its key and the
+    /// value previously associated with it, or `None` if the map contains no *uncommon* entries.
+    ///
+    /// Which entry is removed is unspecified, and may differ between runs of the program.
+    pub fn pop(&mut self) -> Option<(K, V)> {
+        // Dropping the ExtractIf after one entry keeps all entries it hasn't visited
+        self.inner.extract_if(|_, _| true).next()
+    }
+
+    /// Associates a key with a value in the map, and returns the value previously associated with
+    /// that key if it was *uncommon*. If it was *common*, returns
+    /// [`C::try_common()`](Commonality::try_common), which is `None` unless the commonality
+    /// provides the *common* value cheaply.
+    ///
+    /// Unlike [`insert()`](Self::insert), this method never calls
+    /// [`C::common()`](Commonality::common), which makes it preferable when the *common* value is
+    /// expensive to construct and the previous value is not needed.
+    pub fn insert_opt(&mut self, key: K, value: V) -> Option<V> {
+        if C::is_common(&value) { self.inner.remove(&key) } else { self.inner.insert(key, value) }
+            .or_else(C::try_common)
+    }
+
+    /// Associates a key with a value in the map, unless the key is already associated with an
+    /// *uncommon* value, and returns a reference to the value now associated with the key.
+    ///
+    /// Keys associated with the *common* value count as vacant. If `value` is *common*, this
+    /// succeeds for such a key and leaves it associated with the *common* value. If the key is
+    /// associated with an *uncommon* value, the map is left unchanged, and the returned error
+    /// contains the key's entry and the value that was not inserted.
+    pub fn try_insert(&mut self, key: K, value: V) -> Result<&V, OccupiedError<'_, K, V, C>> {
+        match self.inner.entry(key) {
+            hash_map::Entry::Occupied(inner) => Err(OccupiedError {
+                entry: Entry {
+                    inner: EntryInner::Occupied { inner },
+                    _commonality: PhantomPtr::default(),
+                },
+                value,
+            }),
+            hash_map::Entry::Vacant(_) if C::is_common(&value) => Ok(&self.common),
+            hash_map::Entry::Vacant(inner) => Ok(inner.insert(value)),
+        }
+    }
+
+    /// Associates a key with the *common* value in the map, and returns the value previously
+    /// associated with that key if it was *uncommon*. If it was *common*, returns
+    /// [`C::try_common()`](Commonality::try_common), which is `None` unless the commonality
+    /// provides the *common* value cheaply.
+    ///
+    /// Unlike [`remove()`](Self::remove), this method never calls
+    /// [`C::common()`](Commonality::common).
+    pub fn remove_opt<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.inner.remove(key).or_else(C::try_common)
+    }
+
+    /// Returns true if the given value is *uncommon*, meaning that inserting it would store an
+    /// entry in the map.
+    pub fn would_store(&self, value: &V) -> bool {
+        !C::is_common(value)
+    }
+
+    /// Returns the number of *uncommon* entries the map would contain after associating the given
+    /// key with the given value, without modifying the map.
+    pub fn len_after_insert<Q>(&self, key: &Q, value: &V) -> usize
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        match (self.contains_key(key), self.would_store(value)) {
+            (false, true) => self.len() + 1,
+            (true, false) => self.len() - 1,
+            _ => self.len(),
+        }
+    }
+
     /// Gets the given key's associated entry in the map for in-place manipulation.
+    ///
+    /// Unless the commonality is [CHEAP](Commonality::CHEAP), the *common* value is not
+    /// constructed for a vacant entry until the entry is modified.
     pub fn entry(&mut self, key: K) -> Entry<'_, K, V, C> {
         Entry {
             inner: match self.inner.entry(key) {
                 hash_map::Entry::Occupied(inner) => EntryInner::Occupied { inner },
-                hash_map::Entry::Vacant(inner) => EntryInner::Vacant { inner, value: C::common() },
+                hash_map::Entry::Vacant(inner) if C::CHEAP => {
+                    EntryInner::Vacant { inner, value: C::common() }
+                }
+                hash_map::Entry::Vacant(inner) => {
+                    EntryInner::Implicit { inner, common: &self.common }
+                }
             },
             _commonality: PhantomPtr::default(),
         }
     }
+
+    /// Gets the given key's associated entry in the map for in-place manipulation, if the key is
+    /// associated with an *uncommon* value. Otherwise, returns the key back to the caller, without
+    /// constructing the *common* value.
+    ///
+    /// This is useful when the key is expensive to construct and usually associated with the
+    /// *common* value, since the key can be reused if no entry is needed:
+    ///
+    /// ```
+    /// # use total_maps::TotalHashMap;
+    /// let mut counts = TotalHashMap::<String, u32>::new();
+    /// counts.insert("foo".to_owned(), 1);
+    /// for name in ["foo", "bar"] {
+    ///     match counts.entry_or_return(name.to_owned()) {
+    ///         Ok(mut count) => *count += 1,
+    ///         Err(name) => println!("no entry for {name}"),
+    ///     }
+    /// }
+    /// assert_eq!(counts.get("foo"), &2);
+    /// assert!(!counts.contains_key("bar"));
+    /// ```
+    pub fn entry_or_return(&mut self, key: K) -> Result<Entry<'_, K, V, C>, K> {
+        match self.inner.entry(key) {
+            hash_map::Entry::Occupied(inner) => Ok(Entry {
+                inner: EntryInner::Occupied { inner },
+                _commonality: PhantomPtr::default(),
+            }),
+            hash_map::Entry::Vacant(inner) => Err(inner.into_key()),
+        }
+    }
+
+    /// Gets a mutable view of the value associated with the given key, if the key is associated
+    /// with an *uncommon* value. Unlike [`entry()`](Self::entry), this borrows the key rather than
+    /// taking ownership of it.
+    ///
+    /// The value stays in the map while the returned view exists. When the view is dropped, the
+    /// entry is removed if its value is then *common*. See [RefMut] for details.
+    pub fn get_mut<'a, 'b, Q>(&'a mut self, key: &'b Q) -> Option<RefMut<'a, 'b, K, Q, V, C, S>>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        if !self.inner.contains_key(key) {
+            return None;
+        }
+        Some(RefMut { map: self, key })
+    }
+
+    /// Gets the entries for several distinct keys at once for in-place manipulation, if all of the
+    /// keys are associated with *uncommon* values. Returns `None`, leaving the map unchanged, if
+    /// any key is associated with the *common* value or if any two keys are equal.
+    ///
+    /// The entries are taken out of the map while the returned view exists, and put back when it is
+    /// dropped, except for those whose values are then *common*. See [ManyMut] for details.
+    pub fn get_many_mut<Q, const N: usize>(
+        &mut self,
+        keys: [&Q; N],
+    ) -> Option<ManyMut<'_, K, V, C, S, N>>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        for (i, key) in keys.iter().enumerate() {
+            if !self.inner.contains_key(*key) || keys[..i].contains(key) {
+                return None;
+            }
+        }
+        let entries = keys.map(|key| self.inner.remove_entry(key).expect("key was just checked"));
+        Some(ManyMut { map: self, entries: Some(entries) })
+    }
 }
 
 /// A view into a single entry in a [TotalHashMap].
 ///
-/// This view is constructed from [TotalHashMap::entry].
+/// The entry's value may be changed freely through the view. When the view is dropped, the entry
+/// is removed from the map if its value is *common*, or stored if it is *uncommon*. This also
+/// happens if the view is dropped while unwinding from a panic, so the map remains valid (holding
+/// whatever value the entry had when the panic occurred) if the panic is caught. However, if the
+/// [commonality](Commonality) itself panics during unwinding, the process aborts.
+///
+/// There is deliberately no method that returns a bare `&mut V` defaulting to the *common* value:
+/// the map couldn't tell whether the value was left *common*, and pruning it lazily would break
+/// the invariant that [len](TotalHashMap::len) and iteration rely on. An entry whose value is never
+/// changed is not stored at all, so `*map.entry(key)` serves the same purpose.
+///
+/// This view is constructed from [TotalHashMap::entry] or [TotalHashMap::entry_or_return].
 pub struct Entry<'a, K, V, C: Commonality<V> = DefaultCommonality> {
     inner: EntryInner<'a, K, V>,
     _commonality: PhantomPtr<C>,
@@ -141,16 +564,26 @@ impl<K, V, C: Commonality<V>> Deref for Entry<'_, K, V, C> {
         match &self.inner {
             EntryInner::Occupied { inner } => inner.get(),
             EntryInner::Vacant { value, .. } => value,
+            EntryInner::Implicit { common, .. } => common,
             EntryInner::Dropping => unreachable!(),
         }
     }
 }
 impl<K, V, C: Commonality<V>> DerefMut for Entry<'_, K, V, C> {
     fn deref_mut(&mut self) -> &mut Self::Target {
+        if let EntryInner::Implicit { .. } = self.inner {
+            let value = C::common();
+            let EntryInner::Implicit { inner, .. } =
+                mem::replace(&mut self.inner, EntryInner::Dropping)
+            else {
+                unreachable!();
+            };
+            self.inner = EntryInner::Vacant { inner, value };
+        }
         match &mut self.inner {
             EntryInner::Occupied { inner } => inner.get_mut(),
             EntryInner::Vacant { value, .. } => value,
-            EntryInner::Dropping => unreachable!(),
+            EntryInner::Implicit { .. } | EntryInner::Dropping => unreachable!(),
         }
     }
 }
@@ -168,6 +601,7 @@ impl<K, V, C: Commonality<V>> Drop for Entry<'_, K, V, C> {
                     inner.insert(value);
                 }
             }
+            EntryInner::Implicit { .. } => {}
             EntryInner::Dropping => unreachable!(),
         }
     }
@@ -179,22 +613,447 @@ impl<'a, K: Debug, V: Debug, C: Commonality<V>> Debug for Entry<'a, K, V, C> {
         match &self.inner {
             EntryInner::Occupied { inner } => f.field(inner.key()).field(inner.get()),
             EntryInner::Vacant { inner, value } => f.field(inner.key()).field(value),
+            EntryInner::Implicit { inner, common } => f.field(inner.key()).field(common),
             EntryInner::Dropping => &mut f,
         };
         f.finish()
     }
 }
 
+/// The error returned by [TotalHashMap::try_insert] when the key is already associated with an
+/// *uncommon* value.
+pub struct OccupiedError<'a, K, V, C: Commonality<V> = DefaultCommonality> {
+    /// The entry for the key, which holds its existing *uncommon* value.
+    pub entry: Entry<'a, K, V, C>,
+    /// The value that was not inserted.
+    pub value: V,
+}
+
+impl<K: Debug, V: Debug, C: Commonality<V>> Debug for OccupiedError<'_, K, V, C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OccupiedError")
+            .field("entry", &self.entry)
+            .field("value", &self.value)
+            .finish()
+    }
+}
+impl<K, V, C: Commonality<V>> Display for OccupiedError<'_, K, V, C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "key is already associated with an uncommon value")
+    }
+}
+impl<K: Debug, V: Debug, C: Commonality<V>> Error for OccupiedError<'_, K, V, C> {}
+
+/// A view into several distinct *uncommon* entries in a [TotalHashMap].
+///
+/// The entries' values may be changed freely through [`values_mut()`](Self::values_mut). The
+/// entries are held outside the map while the view exists; when the view is dropped, each entry is
+/// put back into the map if its value is *uncommon*, and otherwise its key is left associated with
+/// the *common* value. If the view is leaked, all of its keys are reset to the *common* value.
+///
+/// This view is constructed from [TotalHashMap::get_many_mut].
+pub struct ManyMut<'a, K, V, C, S, const N: usize>
+where
+    K: Eq + Hash,
+    C: Commonality<V>,
+    S: BuildHasher,
+{
+    map: &'a mut TotalHashMap<K, V, C, S>,
+    entries: Option<[(K, V); N]>, // always Some until dropped
+}
+
+impl<K: Eq + Hash, V, C: Commonality<V>, S: BuildHasher, const N: usize>
+    ManyMut<'_, K, V, C, S, N>
+{
+    /// Returns the entries' keys, in the order in which they were requested.
+    pub fn keys(&self) -> [&K; N] {
+        self.entries.as_ref().expect("entries already dropped").each_ref().map(|(key, _)| key)
+    }
+    /// Returns mutable references to the entries' values, in the order in which they were
+    /// requested.
+    pub fn values_mut(&mut self) -> [&mut V; N] {
+        self.entries.as_mut().expect("entries already dropped").each_mut().map(|(_, value)| value)
+    }
+}
+
+impl<K: Eq + Hash, V, C: Commonality<V>, S: BuildHasher, const N: usize> Drop
+    for ManyMut<'_, K, V, C, S, N>
+{
+    fn drop(&mut self) {
+        for (key, value) in self.entries.take().into_iter().flatten() {
+            if !C::is_common(&value) {
+                self.map.inner.insert(key, value);
+            }
+        }
+    }
+}
+
+impl<K, V, C, S, const N: usize> Debug for ManyMut<'_, K, V, C, S, N>
+where
+    K: Eq + Hash + Debug,
+    V: Debug,
+    C: Commonality<V>,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let entries = self.entries.iter().flatten().map(|(key, value)| (key, value));
+        f.debug_map().entries(entries).finish()
+    }
+}
+
 enum EntryInner<'a, K, V> {
     Occupied { inner: hash_map::OccupiedEntry<'a, K, V> },
     Vacant { inner: hash_map::VacantEntry<'a, K, V>, value: V },
+    // A vacant entry whose value is still the *common* value, which hasn't been constructed yet
+    Implicit { inner: hash_map::VacantEntry<'a, K, V>, common: &'a V },
     Dropping,
 }
 
+impl<K: Eq + Hash, V, C: Commonality<V>, S: BuildHasher> TotalHashMap<K, V, C, S> {
+    /// Gets the given key's associated entry in the map for in-place manipulation, without taking
+    /// ownership of the key.
+    ///
+    /// The key is converted into an owned key only if a new *uncommon* entry is stored when the
+    /// returned view is dropped. See [EntryRef] for details.
+    pub fn entry_ref<'a, 'b, Q>(&'a mut self, key: &'b Q) -> EntryRef<'a, 'b, K, Q, V, C, S>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ToOwned<Owned = K> + ?Sized,
+    {
+        self.entry_ref_with(key, C::common)
+    }
+    /// Gets the given key's associated entry in the map for in-place manipulation, without taking
+    /// ownership of the key. If the key is associated with the *common* value, the entry's value
+    /// is initialized to `default` instead.
+    ///
+    /// The key is converted into an owned key only if a new *uncommon* entry is stored when the
+    /// returned view is dropped. See [EntryRef] for details.
+    pub fn entry_ref_or_insert<'a, 'b, Q>(
+        &'a mut self,
+        key: &'b Q,
+        default: V,
+    ) -> EntryRef<'a, 'b, K, Q, V, C, S>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ToOwned<Owned = K> + ?Sized,
+    {
+        self.entry_ref_with(key, || default)
+    }
+    // Only calls `default` if the key is associated with the *common* value
+    fn entry_ref_with<'a, 'b, Q>(
+        &'a mut self,
+        key: &'b Q,
+        default: impl FnOnce() -> V,
+    ) -> EntryRef<'a, 'b, K, Q, V, C, S>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ToOwned<Owned = K> + ?Sized,
+    {
+        let vacant = if self.inner.contains_key(key) { None } else { Some(default()) };
+        EntryRef { map: self, key, vacant }
+    }
+}
+
+/// A view into a single entry in a [TotalHashMap], identified by a borrowed key.
+///
+/// Like an [Entry], the view dereferences to the entry's value, which may be changed freely. When
+/// the view is dropped, the entry is removed from the map if its value is *common*, or stored if it
+/// is *uncommon*; only in the latter case, and only if the map didn't already contain an entry for
+/// the key, is the borrowed key converted into an owned key.
+///
+/// Each access to an entry that is stored in the map looks the key up again.
+///
+/// This view is constructed from [TotalHashMap::entry_ref] or [TotalHashMap::entry_ref_or_insert].
+pub struct EntryRef<'a, 'b, K, Q, V, C = DefaultCommonality, S = RandomState>
+where
+    K: Eq + Hash + Borrow<Q>,
+    Q: Eq + Hash + ToOwned<Owned = K> + ?Sized,
+    C: Commonality<V>,
+    S: BuildHasher,
+{
+    map: &'a mut TotalHashMap<K, V, C, S>,
+    key: &'b Q,
+    vacant: Option<V>, // the entry's value, if the map doesn't contain an entry for the key
+}
+
+impl<K, Q, V, C, S> Deref for EntryRef<'_, '_, K, Q, V, C, S>
+where
+    K: Eq + Hash + Borrow<Q>,
+    Q: Eq + Hash + ToOwned<Owned = K> + ?Sized,
+    C: Commonality<V>,
+    S: BuildHasher,
+{
+    type Target = V;
+    fn deref(&self) -> &Self::Target {
+        match &self.vacant {
+            Some(value) => value,
+            None => &self.map.inner[self.key],
+        }
+    }
+}
+impl<K, Q, V, C, S> DerefMut for EntryRef<'_, '_, K, Q, V, C, S>
+where
+    K: Eq + Hash + Borrow<Q>,
+    Q: Eq + Hash + ToOwned<Owned = K> + ?Sized,
+    C: Commonality<V>,
+    S: BuildHasher,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        match &mut self.vacant {
+            Some(value) => value,
+            None => self.map.inner.get_mut(self.key).unwrap(),
+        }
+    }
+}
+
+impl<K, Q, V, C, S> Drop for EntryRef<'_, '_, K, Q, V, C, S>
+where
+    K: Eq + Hash + Borrow<Q>,
+    Q: Eq + Hash + ToOwned<Owned = K> + ?Sized,
+    C: Commonality<V>,
+    S: BuildHasher,
+{
+    fn drop(&mut self) {
+        match self.vacant.take() {
+            Some(value) => {
+                if !C::is_common(&value) {
+                    self.map.inner.insert(self.key.to_owned(), value);
+                }
+            }
+            None => {
+                if C::is_common(&self.map.inner[self.key]) {
+                    self.map.inner.remove(self.key);
+                }
+            }
+        }
+    }
+}
+
+impl<K, Q, V, C, S> Debug for EntryRef<'_, '_, K, Q, V, C, S>
+where
+    K: Eq + Hash + Borrow<Q>,
+    Q: Eq + Hash + ToOwned<Owned = K> + Debug + ?Sized,
+    V: Debug,
+    C: Commonality<V>,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("EntryRef").field(&self.key).field(&**self).finish()
+    }
+}
+
+/// A mutable view of the value of an *uncommon* entry in a [TotalHashMap], identified by a borrowed
+/// key.
+///
+/// The view dereferences to the entry's value, which stays in the map and may be changed freely.
+/// When the view is dropped, the entry is removed from the map if its value is *common*. Each
+/// access looks the key up again.
+///
+/// If the view is leaked after its value is set to the *common* value, the entry remains stored,
+/// and the map must be fixed with [`repair()`](TotalHashMap::repair); no *uncommon* value is lost.
+///
+/// This view is constructed from [TotalHashMap::get_mut].
+pub struct RefMut<'a, 'b, K, Q, V, C = DefaultCommonality, S = RandomState>
+where
+    K: Eq + Hash + Borrow<Q>,
+    Q: Eq + Hash + ?Sized,
+    C: Commonality<V>,
+    S: BuildHasher,
+{
+    map: &'a mut TotalHashMap<K, V, C, S>,
+    key: &'b Q,
+}
+
+impl<K, Q, V, C, S> Deref for RefMut<'_, '_, K, Q, V, C, S>
+where
+    K: Eq + Hash + Borrow<Q>,
+    Q: Eq + Hash + ?Sized,
+    C: Commonality<V>,
+    S: BuildHasher,
+{
+    type Target = V;
+    fn deref(&self) -> &Self::Target {
+        &self.map.inner[self.key]
+    }
+}
+impl<K, Q, V, C, S> DerefMut for RefMut<'_, '_, K, Q, V, C, S>
+where
+    K: Eq + Hash + Borrow<Q>,
+    Q: Eq + Hash + ?Sized,
+    C: Commonality<V>,
+    S: BuildHasher,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.map.inner.get_mut(self.key).unwrap()
+    }
+}
+
+impl<K, Q, V, C, S> Drop for RefMut<'_, '_, K, Q, V, C, S>
+where
+    K: Eq + Hash + Borrow<Q>,
+    Q: Eq + Hash + ?Sized,
+    C: Commonality<V>,
+    S: BuildHasher,
+{
+    fn drop(&mut self) {
+        if C::is_common(&self.map.inner[self.key]) {
+            self.map.inner.remove(self.key);
+        }
+    }
+}
+
+impl<K, Q, V, C, S> Debug for RefMut<'_, '_, K, Q, V, C, S>
+where
+    K: Eq + Hash + Borrow<Q>,
+    Q: Eq + Hash + Debug + ?Sized,
+    V: Debug,
+    C: Commonality<V>,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("RefMut").field(&self.key).field(&**self).finish()
+    }
+}
+
+// --------------------------------------------------------------------------
+// Accumulation
+
+impl<K: Eq + Hash, V: AddAssign, C: Commonality<V>, S: BuildHasher> TotalHashMap<K, V, C, S> {
+    /// Adds `delta` to the value associated with the given key. If the sum is *common*, the key is
+    /// reset to the *common* value.
+    pub fn add(&mut self, key: K, delta: V) {
+        *self.entry(key) += delta;
+    }
+}
+
+#[cfg(feature = "num-traits")]
+impl<K, V, C, S> TotalHashMap<K, V, C, S>
+where
+    K: Eq + Hash,
+    V: AddAssign + One,
+    C: Commonality<V>,
+    S: BuildHasher,
+{
+    /// Adds one to the value associated with the given key. If the sum is *common*, the key is
+    /// reset to the *common* value.
+    pub fn increment(&mut self, key: K) {
+        self.add(key, V::one());
+    }
+}
+
+#[cfg(feature = "num-traits")]
+impl<K, V, C, S> TotalHashMap<K, V, C, S>
+where
+    K: Eq + Hash,
+    V: CheckedSub,
+    C: Commonality<V>,
+    S: BuildHasher,
+{
+    /// Subtracts `delta` from the value associated with the given key. If the difference is
+    /// *common*, the key is reset to the *common* value.
+    ///
+    /// Returns `None`, leaving the map unchanged, if the subtraction would overflow (for example,
+    /// if it would take an unsigned value below zero). Otherwise, returns true if the key is still
+    /// associated with an *uncommon* value.
+    pub fn sub(&mut self, key: K, delta: V) -> Option<bool> {
+        let mut entry = self.entry(key);
+        *entry = entry.checked_sub(&delta)?;
+        Some(!C::is_common(&entry))
+    }
+}
+
+#[cfg(feature = "num-traits")]
+impl<K, V, C, S> TotalHashMap<K, V, C, S>
+where
+    K: Eq + Hash,
+    V: CheckedSub + One,
+    C: Commonality<V>,
+    S: BuildHasher,
+{
+    /// Subtracts one from the value associated with the given key. If the difference is *common*,
+    /// the key is reset to the *common* value.
+    ///
+    /// Returns `None`, leaving the map unchanged, if the subtraction would overflow. Otherwise,
+    /// returns true if the key is still associated with an *uncommon* value.
+    pub fn decrement(&mut self, key: K) -> Option<bool> {
+        self.sub(key, V::one())
+    }
+}
+
+// --------------------------------------------------------------------------
+// Filtering
+
+impl<K, V, C, S> TotalHashMap<K, V, C, S> {
+    /// Resets all *uncommon* entries for which the predicate returns true to the *common* value.
+    pub fn reset_where<F: FnMut(&K, &V) -> bool>(&mut self, mut pred: F) {
+        self.inner.retain(|key, value| !pred(key, value))
+    }
+}
+impl<K, V, C: Commonality<V>, S> TotalHashMap<K, V, C, S> {
+    /// Retains only the *uncommon* entries for which the predicate returns true, resetting the
+    /// others to the *common* value. Entries are visited in arbitrary order.
+    ///
+    /// The predicate may mutate each value. An entry whose value is *common* after the predicate
+    /// returns is reset to the *common* value, even if the predicate returned true.
+    pub fn retain<F: FnMut(&K, &mut V) -> bool>(&mut self, mut f: F) {
+        self.inner.retain(|key, value| f(key, value) && !C::is_common(value))
+    }
+
+    /// Creates an iterator that visits all *uncommon* entries in arbitrary order, and removes and
+    /// yields those for which the predicate returns true, resetting them to the *common* value.
+    ///
+    /// The predicate may mutate each value. An entry whose value is *common* after the predicate
+    /// returns is reset to the *common* value regardless of the result, and is never yielded.
+    ///
+    /// Entries are only visited as the iterator advances; if it is dropped before being fully
+    /// consumed, the remaining entries are left in the map untouched.
+    pub fn extract_if<'a, F>(
+        &'a mut self,
+        mut pred: F,
+    ) -> ExtractIf<'a, K, V, impl FnMut(&K, &mut V) -> bool + 'a>
+    where
+        F: FnMut(&K, &mut V) -> bool + 'a,
+    {
+        ExtractIf {
+            inner: self.inner.extract_if(move |key, value| pred(key, value) || C::is_common(value)),
+            is_common: C::is_common,
+        }
+    }
+}
+impl<K: Eq + Hash, V, C: Commonality<V>, S: BuildHasher> TotalHashMap<K, V, C, S> {
+    /// Retains only the given keys' entries for which the predicate returns true, resetting the
+    /// others to the *common* value. Entries for keys not in `keys` are left untouched.
+    ///
+    /// Unlike a whole-map retain, the predicate is also called for keys associated with the
+    /// *common* value, and may mutate that value to make the entry *uncommon*. Any entry whose
+    /// value is *common* after the predicate returns is reset to the *common* value.
+    pub fn retain_keys<I, F>(&mut self, keys: I, mut f: F)
+    where
+        I: IntoIterator<Item = K>,
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        for key in keys {
+            match self.inner.get_mut(&key) {
+                Some(value) => {
+                    if !f(&key, value) || C::is_common(value) {
+                        self.inner.remove(&key);
+                    }
+                }
+                None => {
+                    let mut value = C::common();
+                    if f(&key, &mut value) && !C::is_common(&value) {
+                        self.inner.insert(key, value);
+                    }
+                }
+            }
+        }
+    }
+}
+
 // --------------------------------------------------------------------------
 // Iteration
 
-impl<K, V, C> TotalHashMap<K, V, C> {
+impl<K, V, C, S> TotalHashMap<K, V, C, S> {
     /// An iterator over all keys associated with *uncommon* values in the map, in arbitrary order.
     pub fn keys(&self) -> Keys<'_, K, V> {
         Keys(self.inner.keys())
@@ -204,6 +1063,22 @@ impl<K, V, C> TotalHashMap<K, V, C> {
     pub fn into_keys(self) -> IntoKeys<K, V> {
         IntoKeys(self.inner.into_keys())
     }
+    /// Returns all keys associated with *uncommon* values in the map, in arbitrary order. Viewing
+    /// the map as a function, this is its support; for a sparse vector, it is the set of nonzero
+    /// indices.
+    pub fn support(&self) -> Vec<&K> {
+        self.keys().collect()
+    }
+    /// Returns all keys associated with *uncommon* values in the map, in sorted order. See
+    /// [`support()`](Self::support).
+    pub fn support_sorted(&self) -> Vec<&K>
+    where
+        K: Ord,
+    {
+        let mut support = self.support();
+        support.sort_unstable();
+        support
+    }
     /// An iterator over all *uncommon* values in the map, in arbitrary order.
     pub fn values(&self) -> Values<'_, K, V> {
         Values(self.inner.values())
@@ -216,28 +1091,51 @@ impl<K, V, C> TotalHashMap<K, V, C> {
     pub fn iter(&self) -> Iter<'_, K, V> {
         Iter(self.inner.iter())
     }
+    /// Returns the full contents of the map: the *common* value, and an iterator over all
+    /// *uncommon* entries in arbitrary order. All keys not produced by the iterator are associated
+    /// with the *common* value.
+    pub fn describe(&self) -> (&V, Iter<'_, K, V>) {
+        (&self.common, self.iter())
+    }
     /// Resets all entries in the map to the *common* value, and returns all previously *uncommon*
-    /// entries as an iterator.
+    /// entries as an iterator, in arbitrary order.
+    ///
+    /// The map is reset even if the returned iterator is dropped before being fully consumed; any
+    /// remaining entries are dropped along with it.
     pub fn drain(&mut self) -> Drain<'_, K, V> {
-        Drain(self.inner.drain())
+        Drain(Some(self.inner.drain()))
     }
+}
 
-    // We don't offer `values_mut` or `iter_mut` because the mutable references they expose could be
-    // used to violate the invariant that only uncommon values are stored in the map. Trying to
-    // restore the invariant in the iterator's Drop impl would be unsound because nothing prevents
-    // the mutable references from outliving the iterator. We could use the "lending iterator"
-    // pattern (with GATs) to implement `values_mut` and `iter_mut` soundly, but we'd be divorcing
-    // from standard Iterators and all the goodness that comes with them (e.g. for-loops).
+// A standard `values_mut` iterator could hand out mutable references that outlive it, so restoring
+// the invariant in its Drop impl would be unsound. Instead, `values_mut` returns a "lending
+// iterator" whose references can't outlive the next call to `next`, at the cost of for-loops and
+// iterator adapters.
+impl<K: Eq + Hash, V, C: Commonality<V>, S: BuildHasher> TotalHashMap<K, V, C, S> {
+    /// A lending iterator over mutable references to all *uncommon* values in the map, in arbitrary
+    /// order. See [IterMut] for details.
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V, C, S> {
+        ValuesMut(self.iter_mut())
+    }
+    /// A lending iterator over all *uncommon* entries in the map, with mutable references to the
+    /// values, in arbitrary order. See [IterMut] for details.
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V, C, S> {
+        let map = &mut self.inner as *mut HashMap<K, V, S>;
+        // SAFETY: the map outlives the iterator, which borrows it mutably for its whole lifetime;
+        // the inner iterator is dropped before the map is used again through this pointer
+        let iter = unsafe { (*map).iter_mut() };
+        IterMut { map, iter: Some(iter), touched: false, _commonality: PhantomPtr::default() }
+    }
 }
 
-impl<K, V, C> IntoIterator for TotalHashMap<K, V, C> {
+impl<K, V, C, S> IntoIterator for TotalHashMap<K, V, C, S> {
     type Item = (K, V);
     type IntoIter = IntoIter<K, V>;
     fn into_iter(self) -> Self::IntoIter {
         IntoIter(self.inner.into_iter())
     }
 }
-impl<'a, K, V, C> IntoIterator for &'a TotalHashMap<K, V, C> {
+impl<'a, K, V, C, S> IntoIterator for &'a TotalHashMap<K, V, C, S> {
     type Item = (&'a K, &'a V);
     type IntoIter = Iter<'a, K, V>;
     fn into_iter(self) -> Self::IntoIter {
@@ -247,13 +1145,19 @@ impl<'a, K, V, C> IntoIterator for &'a TotalHashMap<K, V, C> {
 
 /// An iterator over the keys associated with *uncommon* values in a [TotalHashMap].
 ///
-/// This iterator is created by [TotalHashMap::keys].
-pub struct Keys<'a, K, V>(hash_map::Keys<'a, K, V>);
+/// This iterator is created by [TotalHashMap::keys] and
+/// [TotalHashMapDyn::keys](crate::TotalHashMapDyn::keys).
+pub struct Keys<'a, K, V>(pub(crate) hash_map::Keys<'a, K, V>);
 impl<K, V> Clone for Keys<'_, K, V> {
     fn clone(&self) -> Self {
         Self(self.0.clone())
     }
 }
+impl<'a, K, V> Default for Keys<'a, K, V> {
+    fn default() -> Self {
+        Self(Default::default())
+    }
+}
 impl<'a, K, V> Iterator for Keys<'a, K, V> {
     type Item = &'a K;
     fn next(&mut self) -> Option<Self::Item> {
@@ -279,6 +1183,11 @@ impl<K: Debug, V: Debug> Debug for Keys<'_, K, V> {
 ///
 /// This iterator is created by [TotalHashMap::into_keys].
 pub struct IntoKeys<K, V>(hash_map::IntoKeys<K, V>);
+impl<K, V> Default for IntoKeys<K, V> {
+    fn default() -> Self {
+        Self(Default::default())
+    }
+}
 impl<K, V> Iterator for IntoKeys<K, V> {
     type Item = K;
     fn next(&mut self) -> Option<Self::Item> {
@@ -297,13 +1206,19 @@ impl<K, V> FusedIterator for IntoKeys<K, V> {}
 
 /// An iterator over the *uncommon* values in a [TotalHashMap].
 ///
-/// This iterator is created by [TotalHashMap::values].
-pub struct Values<'a, K, V>(hash_map::Values<'a, K, V>);
+/// This iterator is created by [TotalHashMap::values] and
+/// [TotalHashMapDyn::values](crate::TotalHashMapDyn::values).
+pub struct Values<'a, K, V>(pub(crate) hash_map::Values<'a, K, V>);
 impl<K, V> Clone for Values<'_, K, V> {
     fn clone(&self) -> Self {
         Self(self.0.clone())
     }
 }
+impl<'a, K, V> Default for Values<'a, K, V> {
+    fn default() -> Self {
+        Self(Default::default())
+    }
+}
 impl<'a, K, V> Iterator for Values<'a, K, V> {
     type Item = &'a V;
     fn next(&mut self) -> Option<Self::Item> {
@@ -329,6 +1244,11 @@ impl<K: Debug, V: Debug> Debug for Values<'_, K, V> {
 ///
 /// This iterator is created by [TotalHashMap::into_values].
 pub struct IntoValues<K, V>(hash_map::IntoValues<K, V>);
+impl<K, V> Default for IntoValues<K, V> {
+    fn default() -> Self {
+        Self(Default::default())
+    }
+}
 impl<K, V> Iterator for IntoValues<K, V> {
     type Item = V;
     fn next(&mut self) -> Option<Self::Item> {
@@ -347,13 +1267,19 @@ impl<K, V> FusedIterator for IntoValues<K, V> {}
 
 /// An iterator over the *uncommon* entries in a [TotalHashMap].
 ///
-/// This iterator is created by [TotalHashMap::iter].
-pub struct Iter<'a, K, V>(hash_map::Iter<'a, K, V>);
+/// This iterator is created by [TotalHashMap::iter] and
+/// [TotalHashMapDyn::iter](crate::TotalHashMapDyn::iter).
+pub struct Iter<'a, K, V>(pub(crate) hash_map::Iter<'a, K, V>);
 impl<K, V> Clone for Iter<'_, K, V> {
     fn clone(&self) -> Self {
         Self(self.0.clone())
     }
 }
+impl<'a, K, V> Default for Iter<'a, K, V> {
+    fn default() -> Self {
+        Self(Default::default())
+    }
+}
 impl<'a, K, V> Iterator for Iter<'a, K, V> {
     type Item = (&'a K, &'a V);
     fn next(&mut self) -> Option<Self::Item> {
@@ -379,6 +1305,11 @@ impl<K: Debug, V: Debug> Debug for Iter<'_, K, V> {
 ///
 /// This iterator is created by [TotalHashMap]'s implementation of [IntoIterator].
 pub struct IntoIter<K, V>(hash_map::IntoIter<K, V>);
+impl<K, V> Default for IntoIter<K, V> {
+    fn default() -> Self {
+        Self(Default::default())
+    }
+}
 impl<K, V> Iterator for IntoIter<K, V> {
     type Item = (K, V);
     fn next(&mut self) -> Option<Self::Item> {
@@ -397,35 +1328,199 @@ impl<K, V> FusedIterator for IntoIter<K, V> {}
 
 /// A draining iterator over the *uncommon* entries in a [TotalHashMap].
 ///
-/// This iterator is created by [TotalHashMap::drain].
-pub struct Drain<'a, K, V>(hash_map::Drain<'a, K, V>);
+/// This iterator is created by [TotalHashMap::drain]. It yields entries in arbitrary order, and is
+/// not double-ended because the underlying [hash_map::Drain] isn't. When dropped, it removes any
+/// entries that have not yet been yielded.
+///
+/// The [Default] value is an empty iterator that isn't attached to any map.
+pub struct Drain<'a, K, V>(Option<hash_map::Drain<'a, K, V>>);
+impl<K, V> Default for Drain<'_, K, V> {
+    fn default() -> Self {
+        Self(None)
+    }
+}
 impl<K, V> Iterator for Drain<'_, K, V> {
     type Item = (K, V);
     fn next(&mut self) -> Option<Self::Item> {
-        self.0.next()
+        self.0.as_mut()?.next()
     }
     fn size_hint(&self) -> (usize, Option<usize>) {
-        self.0.size_hint()
+        self.0.as_ref().map_or((0, Some(0)), |inner| inner.size_hint())
     }
 }
 impl<K, V> ExactSizeIterator for Drain<'_, K, V> {
     fn len(&self) -> usize {
-        self.0.len()
+        self.0.as_ref().map_or(0, |inner| inner.len())
     }
 }
 impl<K, V> FusedIterator for Drain<'_, K, V> {}
 
+/// An iterator that removes and yields the *uncommon* entries of a [TotalHashMap] that match a
+/// predicate.
+///
+/// This iterator is created by [TotalHashMap::extract_if]. It yields entries in arbitrary order.
+/// `F` is the type of the predicate, including the check that resets entries whose values were
+/// made *common*.
+pub struct ExtractIf<'a, K, V, F: FnMut(&K, &mut V) -> bool> {
+    inner: hash_map::ExtractIf<'a, K, V, F>,
+    is_common: fn(&V) -> bool,
+}
+impl<K, V, F: FnMut(&K, &mut V) -> bool> Iterator for ExtractIf<'_, K, V, F> {
+    type Item = (K, V);
+    fn next(&mut self) -> Option<Self::Item> {
+        let is_common = self.is_common;
+        self.inner.find(|(_, value)| !is_common(value))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.inner.size_hint().1)
+    }
+}
+impl<K, V, F: FnMut(&K, &mut V) -> bool> FusedIterator for ExtractIf<'_, K, V, F> {}
+impl<K, V, F: FnMut(&K, &mut V) -> bool> Debug for ExtractIf<'_, K, V, F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExtractIf").finish_non_exhaustive()
+    }
+}
+
+/// A lending iterator over the *uncommon* entries in a [TotalHashMap], with mutable references to
+/// their values.
+///
+/// This is not an [Iterator]: each entry returned by [`next()`](Self::next) borrows the iterator,
+/// and so must be released before the next entry is requested. The values may be changed
+/// freely, including to the *common* value. The entries stay in the map while the iterator exists;
+/// when the iterator is dropped, if any entry was visited, the map is scanned once and every entry
+/// whose value is now *common* is removed. If the iterator is leaked after a value is set to the
+/// *common* value, that entry remains stored, and the map must be fixed with
+/// [`repair()`](TotalHashMap::repair); no *uncommon* value is lost.
+///
+/// This iterator is created by [TotalHashMap::iter_mut]. It yields entries in arbitrary order.
+pub struct IterMut<'a, K, V, C = DefaultCommonality, S = RandomState>
+where
+    K: Eq + Hash,
+    C: Commonality<V>,
+    S: BuildHasher,
+{
+    // A lending iterator can't be built from `&mut HashMap` alone, since the map has no cursor API,
+    // so the map is reached through a pointer once the inner iterator is done with it.
+    map: *mut HashMap<K, V, S>,
+    iter: Option<hash_map::IterMut<'a, K, V>>,
+    touched: bool,
+    _commonality: PhantomPtr<C>,
+}
+
+// SAFETY: the iterator acts as a `&mut HashMap<K, V, S>`
+unsafe impl<K, V, C, S> Send for IterMut<'_, K, V, C, S>
+where
+    K: Eq + Hash + Send,
+    V: Send,
+    C: Commonality<V>,
+    S: BuildHasher + Send,
+{
+}
+unsafe impl<K, V, C, S> Sync for IterMut<'_, K, V, C, S>
+where
+    K: Eq + Hash + Sync,
+    V: Sync,
+    C: Commonality<V>,
+    S: BuildHasher + Sync,
+{
+}
+
+impl<K: Eq + Hash, V, C: Commonality<V>, S: BuildHasher> IterMut<'_, K, V, C, S> {
+    /// Advances the iterator and returns the next entry, with a mutable reference to its value, or
+    /// `None` once all entries have been visited.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<(&K, &mut V)> {
+        let entry = self.iter.as_mut()?.next()?;
+        self.touched = true;
+        Some(entry)
+    }
+    /// Returns the number of entries that have not yet been visited.
+    pub fn len(&self) -> usize {
+        self.iter.as_ref().map_or(0, ExactSizeIterator::len)
+    }
+    /// Returns true if all entries have been visited.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<K: Eq + Hash, V, C: Commonality<V>, S: BuildHasher> Drop for IterMut<'_, K, V, C, S> {
+    fn drop(&mut self) {
+        self.iter = None;
+        if self.touched {
+            // SAFETY: the inner iterator, and every reference it handed out, is gone
+            unsafe { &mut *self.map }.retain(|_, value| !C::is_common(value));
+        }
+    }
+}
+
+impl<K, V, C, S> Debug for IterMut<'_, K, V, C, S>
+where
+    K: Eq + Hash + Debug,
+    V: Debug,
+    C: Commonality<V>,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("IterMut").field(&self.iter).finish()
+    }
+}
+
+/// A lending iterator over mutable references to the *uncommon* values in a [TotalHashMap].
+///
+/// This iterator is created by [TotalHashMap::values_mut]. It behaves like an [IterMut] that
+/// doesn't expose the keys.
+pub struct ValuesMut<'a, K, V, C = DefaultCommonality, S = RandomState>(IterMut<'a, K, V, C, S>)
+where
+    K: Eq + Hash,
+    C: Commonality<V>,
+    S: BuildHasher;
+
+impl<K: Eq + Hash, V, C: Commonality<V>, S: BuildHasher> ValuesMut<'_, K, V, C, S> {
+    /// Advances the iterator and returns a mutable reference to the next value, or `None` once all
+    /// values have been visited.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<&mut V> {
+        self.0.next().map(|(_, value)| value)
+    }
+    /// Returns the number of values that have not yet been visited.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+    /// Returns true if all values have been visited.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl<K, V, C, S> Debug for ValuesMut<'_, K, V, C, S>
+where
+    K: Eq + Hash,
+    V: Debug,
+    C: Commonality<V>,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ValuesMut").field("len", &self.len()).finish_non_exhaustive()
+    }
+}
+
 // --------------------------------------------------------------------------
 // Population from iterators
 
-impl<K: Eq + Hash, V, C: Commonality<V>> Extend<(K, V)> for TotalHashMap<K, V, C> {
+impl<K: Eq + Hash, V, C: Commonality<V>, S: BuildHasher> Extend<(K, V)>
+    for TotalHashMap<K, V, C, S>
+{
     fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
         for (key, value) in iter {
             self.insert(key, value);
         }
     }
 }
-impl<K: Eq + Hash, V, C: Commonality<V>> FromIterator<(K, V)> for TotalHashMap<K, V, C> {
+impl<K: Eq + Hash, V, C: Commonality<V>, S: BuildHasher + Default> FromIterator<(K, V)>
+    for TotalHashMap<K, V, C, S>
+{
     fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
         let mut this = Self::default();
         this.extend(iter);
@@ -433,10 +1528,275 @@ impl<K: Eq + Hash, V, C: Commonality<V>> FromIterator<(K, V)> for TotalHashMap<K
     }
 }
 
+impl<'a, K, V, C, S> Extend<(&'a K, &'a V)> for TotalHashMap<K, V, C, S>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    C: Commonality<V>,
+    S: BuildHasher,
+{
+    fn extend<T: IntoIterator<Item = (&'a K, &'a V)>>(&mut self, iter: T) {
+        self.extend(iter.into_iter().map(|(key, value)| (key.clone(), value.clone())));
+    }
+}
+impl<'a, K, V, C, S> FromIterator<(&'a K, &'a V)> for TotalHashMap<K, V, C, S>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    C: Commonality<V>,
+    S: BuildHasher + Default,
+{
+    fn from_iter<T: IntoIterator<Item = (&'a K, &'a V)>>(iter: T) -> Self {
+        let mut this = Self::default();
+        this.extend(iter);
+        this
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<K, V, C, S> TotalHashMap<K, V, C, S>
+where
+    K: Eq + Hash + Send,
+    V: Send,
+    C: Commonality<V>,
+    S: BuildHasher + Default + Send,
+{
+    /// Constructs a `TotalHashMap` from a vector of entries, deduplicating the entries and
+    /// discarding *common* values in parallel.
+    ///
+    /// If the vector contains several entries with the same key, the last one wins, as if the
+    /// entries had been inserted in order.
+    pub fn par_from_vec(entries: Vec<(K, V)>) -> Self {
+        // Each entry is tagged with its position, so that merging the partial maps can keep the
+        // last entry for each key regardless of how the work was split
+        fn merge<K: Eq + Hash, V>(
+            mut map: HashMap<K, (usize, V)>,
+            (key, (index, value)): (K, (usize, V)),
+        ) -> HashMap<K, (usize, V)> {
+            match map.entry(key) {
+                hash_map::Entry::Occupied(mut entry) => {
+                    if entry.get().0 < index {
+                        entry.insert((index, value));
+                    }
+                }
+                hash_map::Entry::Vacant(entry) => {
+                    entry.insert((index, value));
+                }
+            }
+            map
+        }
+        let latest = entries
+            .into_par_iter()
+            .enumerate()
+            .map(|(index, (key, value))| (key, (index, value)))
+            .fold(HashMap::new, merge)
+            .reduce(HashMap::new, |lhs, rhs| {
+                let (lhs, rhs) = if lhs.len() < rhs.len() { (rhs, lhs) } else { (lhs, rhs) };
+                rhs.into_iter().fold(lhs, merge)
+            });
+        Self {
+            inner: latest
+                .into_par_iter()
+                .map(|(key, (_, value))| (key, value))
+                .filter(|(_, value)| !C::is_common(value))
+                .collect(),
+            common: C::common(),
+            _commonality: PhantomPtr::default(),
+        }
+    }
+}
+
+impl<K: Eq + Hash + Enumerable + Clone, V, C, S: BuildHasher> TotalHashMap<K, V, C, S> {
+    /// An iterator over all keys associated with the *common* value in the map, in the order of
+    /// [Enumerable::VALUES].
+    ///
+    /// These are exactly the values of `K` not produced by [`keys()`](Self::keys).
+    pub fn common_keys(&self) -> impl Iterator<Item = K> + '_ {
+        K::VALUES.iter().filter(|key| !self.contains_key(key)).cloned()
+    }
+}
+
+// --------------------------------------------------------------------------
+// Statistics
+
+impl<K, V: Eq + Hash + Clone, C, S> TotalHashMap<K, V, C, S> {
+    /// Counts how many *uncommon* entries are associated with each distinct value.
+    ///
+    /// Keys associated with the *common* value are not counted, so the *common* value never appears
+    /// in the result.
+    pub fn value_counts(&self) -> HashMap<V, usize> {
+        let mut counts = HashMap::new();
+        for value in self.values() {
+            *counts.entry(value.clone()).or_default() += 1;
+        }
+        counts
+    }
+    /// Counts, for each distinct value, how many more *uncommon* entries of this map are associated
+    /// with it than *uncommon* entries of `other`. The count is negative if `other` has more.
+    ///
+    /// Keys associated with the *common* value in either map are not counted for that map. Values
+    /// with a difference of zero are omitted from the result.
+    pub fn value_delta(&self, other: &Self) -> HashMap<V, i64> {
+        let mut deltas = HashMap::<V, i64>::new();
+        for value in self.values() {
+            *deltas.entry(value.clone()).or_default() += 1;
+        }
+        for value in other.values() {
+            *deltas.entry(value.clone()).or_default() -= 1;
+        }
+        deltas.retain(|_, delta| *delta != 0);
+        deltas
+    }
+}
+
+// --------------------------------------------------------------------------
+// Transformation
+
+impl<K, V, C, S> TotalHashMap<K, V, C, S> {
+    /// Returns the *uncommon* entries of the map sorted by value, in ascending order (rather than
+    /// by key). Entries with equal values are returned in an unspecified order.
+    pub fn into_sorted_by_value(self) -> Vec<(K, V)>
+    where
+        V: Ord,
+    {
+        self.into_sorted_by_value_by(V::cmp)
+    }
+    /// Returns the *uncommon* entries of the map sorted by value, in descending order. Entries with
+    /// equal values are returned in an unspecified order.
+    pub fn into_sorted_by_value_desc(self) -> Vec<(K, V)>
+    where
+        V: Ord,
+    {
+        self.into_sorted_by_value_by(|lhs, rhs| rhs.cmp(lhs))
+    }
+    /// Returns the *uncommon* entries of the map sorted by value using the comparator `compare`.
+    /// Entries with equal values are returned in an unspecified order.
+    pub fn into_sorted_by_value_by<F>(self, mut compare: F) -> Vec<(K, V)>
+    where
+        F: FnMut(&V, &V) -> Ordering,
+    {
+        let mut entries = Vec::with_capacity(self.len());
+        entries.extend(self.inner);
+        entries.sort_by(|(_, lhs), (_, rhs)| compare(lhs, rhs));
+        entries
+    }
+}
+
+impl<K, V, C, S> TotalHashMap<K, V, C, S> {
+    /// Rebuilds the map with each key transformed by `f`, keeping the same values.
+    ///
+    /// If `f` maps several keys to the same new key, the last of their values wins: entries are
+    /// visited in this map's iteration order, and later entries overwrite earlier ones.
+    ///
+    /// The new map uses the same hasher as this one.
+    pub fn map_keys<K2, F>(self, mut f: F) -> TotalHashMap<K2, V, C, S>
+    where
+        K2: Eq + Hash,
+        F: FnMut(K) -> K2,
+        S: BuildHasher + Clone,
+    {
+        let mut inner = HashMap::with_capacity_and_hasher(self.len(), self.inner.hasher().clone());
+        inner.extend(self.inner.into_iter().map(|(key, value)| (f(key), value)));
+        TotalHashMap { inner, common: self.common, _commonality: PhantomPtr::default() }
+    }
+}
+
+impl<K: Eq + Hash + Clone, V: Eq + Hash, C, S: BuildHasher + Clone> TotalHashMap<K, V, C, S> {
+    /// Viewing both maps as functions, returns their composition `other ∘ self`, which associates
+    /// each key `k` with `other[self[k]]`. The commonality `E` of the result is chosen by the
+    /// caller, and may differ from those of both maps.
+    ///
+    /// Every key that `self` associates with its *common* value `c` is associated with `other[c]`
+    /// in the composition. The result can only represent this if `other[c]` is *common* under `E`;
+    /// this holds, for example, when `other` associates `c` with its own *common* value and `E` is
+    /// the commonality of `other`. In that case the composition is as sparse as `self` or sparser,
+    /// since entries of `self` whose values `other` maps to a *common* value are dropped. If
+    /// instead `other[c]` is *uncommon* under `E`, the composition would associate almost every key
+    /// with an *uncommon* value, which no total map can store, so this returns `None`.
+    ///
+    /// Only the *uncommon* entries of `self` are visited, with one lookup in `other` each.
+    ///
+    /// The new map uses the same hasher as this one.
+    pub fn compose<W, D, E, S2>(
+        &self,
+        other: &TotalHashMap<V, W, D, S2>,
+    ) -> Option<TotalHashMap<K, W, E, S>>
+    where
+        W: Clone,
+        E: Commonality<W>,
+        S2: BuildHasher,
+    {
+        if !E::is_common(other.get(&self.common)) {
+            return None;
+        }
+        let mut inner = HashMap::with_capacity_and_hasher(self.len(), self.inner.hasher().clone());
+        for (key, value) in self.iter() {
+            let value = other.get(value);
+            if !E::is_common(value) {
+                inner.insert(key.clone(), value.clone());
+            }
+        }
+        Some(TotalHashMap { inner, common: E::common(), _commonality: PhantomPtr::default() })
+    }
+}
+
 // --------------------------------------------------------------------------
 // Low-level access
 
-impl<K, V, C: Commonality<V>> TotalHashMap<K, V, C> {
+impl<K, V, C, S> TotalHashMap<K, V, C, S> {
+    /// Returns a reference to the underlying [HashMap] of a [TotalHashMap], which contains only the
+    /// *uncommon* entries.
+    pub fn as_hash_map(&self) -> &HashMap<K, V, S> {
+        &self.inner
+    }
+    /// Unwraps the underlying [HashMap] of a [TotalHashMap], which contains only the *uncommon*
+    /// entries.
+    pub fn into_hash_map(self) -> HashMap<K, V, S> {
+        self.inner
+    }
+}
+
+impl<K, V, C: Commonality<V>, S> TotalHashMap<K, V, C, S> {
+    /// Constructs a map from an underlying [HashMap], checking that it contains no *common*
+    /// values.
+    ///
+    /// Returns an error listing the keys of the offending entries, and containing `inner` itself,
+    /// if `inner` contains any *common* values. To discard the *common* values instead, use the
+    /// [From] conversion.
+    pub fn try_from_hash_map(
+        inner: HashMap<K, V, S>,
+    ) -> Result<Self, CommonValuePresent<K, HashMap<K, V, S>>>
+    where
+        K: Clone,
+    {
+        let keys = inner
+            .iter()
+            .filter(|(_, value)| C::is_common(value))
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        if !keys.is_empty() {
+            return Err(CommonValuePresent { keys, map: inner });
+        }
+        Ok(Self { inner, common: C::common(), _commonality: PhantomPtr::default() })
+    }
+}
+
+impl<K, V, C: Commonality<V>, S> From<HashMap<K, V, S>> for TotalHashMap<K, V, C, S> {
+    /// Constructs a map from an underlying [HashMap], discarding any entries with *common* values.
+    /// The [HashMap]'s allocation is reused.
+    fn from(mut inner: HashMap<K, V, S>) -> Self {
+        inner.retain(|_, value| !C::is_common(value));
+        Self { inner, common: C::common(), _commonality: PhantomPtr::default() }
+    }
+}
+
+impl<K, V, C, S> AsRef<HashMap<K, V, S>> for TotalHashMap<K, V, C, S> {
+    fn as_ref(&self) -> &HashMap<K, V, S> {
+        &self.inner
+    }
+}
+
+impl<K, V, C: Commonality<V>, S> TotalHashMap<K, V, C, S> {
     /// Returns a mutable view into the underlying [HashMap] of a [TotalHashMap], from which
     /// mutating iterators can be obtained by calling [HashMap::values_mut] or [HashMap::iter_mut].
     ///
@@ -444,47 +1804,82 @@ impl<K, V, C: Commonality<V>> TotalHashMap<K, V, C> {
     /// the map temporarily. When the returned view is dropped, all *uncommon* entries will be
     /// removed, restoring the invariant of [TotalHashMap].
     ///
-    /// You don't need this method if you are only mutating individual entries; use the
-    /// [entry][Self::entry] method instead.
-    pub fn as_hash_map_mut(&mut self) -> AsHashMapMut<'_, K, V, C> {
+    /// You don't need this method if you are only mutating individual entries, or mutating values
+    /// in place; use the [entry][Self::entry] or [iter_mut][Self::iter_mut] methods instead.
+    pub fn as_hash_map_mut(&mut self) -> AsHashMapMut<'_, K, V, C, S> {
         AsHashMapMut { map: &mut self.inner, _commonality: PhantomPtr::default() }
     }
+
+    /// Returns true if the map upholds its invariant that no *common* values are stored.
+    ///
+    /// This can only be false if the invariant was circumvented, for example by leaking the view
+    /// returned by [`as_hash_map_mut()`](Self::as_hash_map_mut) with [std::mem::forget] so that its
+    /// cleanup never ran.
+    pub fn is_canonical(&self) -> bool {
+        !self.inner.values().any(C::is_common)
+    }
+    /// Panics if the map stores any *common* values. Does nothing unless debug assertions are
+    /// enabled.
+    pub fn debug_assert_invariant(&self) {
+        debug_assert!(self.is_canonical(), "TotalHashMap stores common values");
+    }
+    /// Removes any stored entries with *common* values, restoring the invariant of [TotalHashMap].
+    pub fn repair(&mut self) {
+        self.inner.retain(|_, value| !C::is_common(value));
+    }
+
+    /// Constructs a map directly from an underlying [HashMap] and the *common* value, without
+    /// scanning the entries.
+    ///
+    /// The caller must ensure that `inner` contains no *common* values, and that `common` is the
+    /// *common* value. Unlike violating a safety contract, violating this contract can't cause
+    /// undefined behavior, but the map will behave incorrectly (for example, its
+    /// [`len()`](Self::len) will count *common* entries) until it is [repaired](Self::repair).
+    /// With debug assertions enabled, this method panics if the contract is violated.
+    pub fn from_parts(inner: HashMap<K, V, S>, common: V) -> Self {
+        debug_assert!(C::is_common(&common), "TotalHashMap::from_parts given an uncommon value");
+        let this = Self { inner, common, _commonality: PhantomPtr::default() };
+        this.debug_assert_invariant();
+        this
+    }
 }
 
 /// A mutable view into the underlying [HashMap] of a [TotalHashMap].
 ///
 /// This view is created by [TotalHashMap::as_hash_map_mut].
-pub struct AsHashMapMut<'a, K, V, C: Commonality<V> = DefaultCommonality> {
-    map: &'a mut HashMap<K, V>,
+pub struct AsHashMapMut<'a, K, V, C: Commonality<V> = DefaultCommonality, S = RandomState> {
+    map: &'a mut HashMap<K, V, S>,
     _commonality: PhantomPtr<C>,
 }
 
-impl<K, V, C: Commonality<V>> Deref for AsHashMapMut<'_, K, V, C> {
-    type Target = HashMap<K, V>;
+impl<K, V, C: Commonality<V>, S> Deref for AsHashMapMut<'_, K, V, C, S> {
+    type Target = HashMap<K, V, S>;
     fn deref(&self) -> &Self::Target {
         self.map
     }
 }
-impl<K, V, C: Commonality<V>> DerefMut for AsHashMapMut<'_, K, V, C> {
+impl<K, V, C: Commonality<V>, S> DerefMut for AsHashMapMut<'_, K, V, C, S> {
     fn deref_mut(&mut self) -> &mut Self::Target {
         self.map
     }
 }
 
-impl<K, V, C: Commonality<V>> Drop for AsHashMapMut<'_, K, V, C> {
+impl<K, V, C: Commonality<V>, S> Drop for AsHashMapMut<'_, K, V, C, S> {
     fn drop(&mut self) {
         self.map.retain(|_, value| !C::is_common(value));
     }
 }
 
-impl<K: Eq + Hash, V: PartialEq, C: Commonality<V>> PartialEq for AsHashMapMut<'_, K, V, C> {
+impl<K: Eq + Hash, V: PartialEq, C: Commonality<V>, S: BuildHasher> PartialEq
+    for AsHashMapMut<'_, K, V, C, S>
+{
     fn eq(&self, other: &Self) -> bool {
         // deliberately ignoring commonality
         self.map == other.map
     }
 }
-impl<K: Eq + Hash, V: Eq, C: Commonality<V>> Eq for AsHashMapMut<'_, K, V, C> {}
-impl<K: Debug, V: Debug, C: Commonality<V>> Debug for AsHashMapMut<'_, K, V, C> {
+impl<K: Eq + Hash, V: Eq, C: Commonality<V>, S: BuildHasher> Eq for AsHashMapMut<'_, K, V, C, S> {}
+impl<K: Debug, V: Debug, C: Commonality<V>, S> Debug for AsHashMapMut<'_, K, V, C, S> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         f.debug_tuple("AsHashMapMut").field(&self.map).finish()
     }
@@ -493,17 +1888,79 @@ impl<K: Debug, V: Debug, C: Commonality<V>> Debug for AsHashMapMut<'_, K, V, C>
 // --------------------------------------------------------------------------
 // Miscellaneous traits
 
-impl<K: Eq + Hash, V: PartialEq, C> PartialEq for TotalHashMap<K, V, C> {
-    fn eq(&self, other: &Self) -> bool {
+impl<K, V, C, S1, S2> PartialEq<TotalHashMap<K, V, C, S2>> for TotalHashMap<K, V, C, S1>
+where
+    K: Eq + Hash,
+    V: PartialEq,
+    S1: BuildHasher,
+    S2: BuildHasher,
+{
+    fn eq(&self, other: &TotalHashMap<K, V, C, S2>) -> bool {
         // Although both self.common and other.common should have the same value (namely,
         // C::common()), we still need to compare them because V's PartialEq impl might not be
         // reflexive
-        self.common == other.common && self.inner == other.inner
+        //
+        // HashMap's own PartialEq impl requires both maps to use the same hasher, so we compare
+        // the entries ourselves
+        self.common == other.common
+            && self.len() == other.len()
+            && self.iter().all(|(key, value)| other.inner.get(key).is_some_and(|v| value == v))
+    }
+}
+impl<K: Eq + Hash, V: Eq, C, S: BuildHasher> Eq for TotalHashMap<K, V, C, S> {}
+
+/// Maps are ordered like the equivalent [TotalBTreeMap](crate::TotalBTreeMap)s: first by their
+/// *common* values, and then lexicographically by their *uncommon* entries in key order.
+///
+/// Each comparison sorts the entries of both maps, taking O(n log n) time.
+impl<K: Ord + Hash, V: PartialOrd, C, S: BuildHasher> PartialOrd for TotalHashMap<K, V, C, S> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        // There is no bound on C: Commonality<V>, so we can't assume self.common == other.common
+        match self.common.partial_cmp(&other.common) {
+            Some(Ordering::Equal) => {}
+            ord => return ord,
+        };
+        self.sorted_entries().partial_cmp(&other.sorted_entries())
+    }
+}
+/// Maps are ordered like the equivalent [TotalBTreeMap](crate::TotalBTreeMap)s: first by their
+/// *common* values, and then lexicographically by their *uncommon* entries in key order.
+///
+/// Each comparison sorts the entries of both maps, taking O(n log n) time.
+impl<K: Ord + Hash, V: Ord, C, S: BuildHasher> Ord for TotalHashMap<K, V, C, S> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // There is no bound on C: Commonality<V>, so we can't assume self.common == other.common
+        self.common
+            .cmp(&other.common)
+            .then_with(|| self.sorted_entries().cmp(&other.sorted_entries()))
+    }
+}
+impl<K: Ord, V, C, S> TotalHashMap<K, V, C, S> {
+    fn sorted_entries(&self) -> Vec<(&K, &V)> {
+        let mut entries = self.iter().collect::<Vec<_>>();
+        entries.sort_unstable_by_key(|&(key, _)| key);
+        entries
+    }
+}
+
+impl<K: Hash, V: Hash, C, S> Hash for TotalHashMap<K, V, C, S> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // There is no bound on C: Commonality<V>, so we can't assume self.common == other.common
+        self.common.hash(state);
+        // Equal maps may iterate their entries in different orders, so the entries are combined
+        // with a commutative operation. Each entry is hashed with a fixed hasher (rather than the
+        // map's own) so that equal maps with different hashers also hash equally.
+        let entries = self.iter().fold(0u64, |sum, entry| {
+            let mut hasher = DefaultHasher::new();
+            entry.hash(&mut hasher);
+            sum.wrapping_add(hasher.finish())
+        });
+        state.write_usize(self.len());
+        state.write_u64(entries);
     }
 }
-impl<K: Eq + Hash, V: Eq, C> Eq for TotalHashMap<K, V, C> {}
 
-impl<K: Debug, V: Debug, C> Debug for TotalHashMap<K, V, C> {
+impl<K: Debug, V: Debug, C, S> Debug for TotalHashMap<K, V, C, S> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         struct Rest;
         impl Debug for Rest {
@@ -511,6 +1968,7 @@ impl<K: Debug, V: Debug, C> Debug for TotalHashMap<K, V, C> {
                 write!(f, "...")
             }
         }
-        f.debug_map().entries(self.iter()).entry(&Rest, &self.common).finish()
+        let (common, entries) = self.describe();
+        f.debug_map().entries(entries).entry(&Rest, common).finish()
     }
 }
@@ -8,18 +8,63 @@
 //! [TotalHashMap] is the main data structure provided by this crate.
 
 use std::{
-    fmt::{self, Debug, Formatter},
+    error::Error,
+    fmt::{self, Debug, Display, Formatter},
     marker::PhantomData,
 };
 
+#[cfg(feature = "derive")]
+pub use total_maps_derive::Commonality;
+
+pub use self::{
+    boolean::{FalseCommonality, TrueCommonality},
+    btree_map::TotalBTreeMap,
+    dynamic::TotalHashMapDyn,
+    empty::EmptyCommonality,
+    float::{NaNCommonality, ThresholdCommonality},
+    frozen::FrozenTotalMap,
+    hash_map::TotalHashMap,
+    lru::BoundedTotalHashMap,
+    option::{NoneCommonality, SparseBTreeMap, SparseHashMap},
+    order_by::{Comparator, OrderBy, TotalBTreeMapBy},
+    pair::PairCommonality,
+    sharded::ShardedTotalHashMap,
+    strict::StrictTotalHashMap,
+};
 #[cfg(feature = "num-traits")]
-pub use self::nonzero::{NonZeroBTreeMap, NonZeroHashMap, ZeroCommonality};
-pub use self::{btree_map::TotalBTreeMap, hash_map::TotalHashMap};
+pub use self::{
+    bounded::{
+        AboveMinBTreeMap, AboveMinHashMap, BelowMaxBTreeMap, BelowMaxHashMap, MaxCommonality,
+        MinCommonality,
+    },
+    nonzero::{NonZeroBTreeMap, NonZeroHashMap, ZeroCommonality},
+};
 
+pub mod boolean;
+#[cfg(feature = "num-traits")]
+pub mod bounded;
 pub mod btree_map;
+pub mod dynamic;
+pub mod empty;
+pub mod float;
+pub mod frozen;
 pub mod hash_map;
+pub mod lru;
 #[cfg(feature = "num-traits")]
 pub mod nonzero;
+pub mod option;
+pub mod order_by;
+pub mod pair;
+#[cfg(feature = "proptest")]
+pub mod proptest;
+#[cfg(feature = "quickcheck")]
+pub mod quickcheck;
+#[cfg(feature = "rkyv")]
+pub mod rkyv;
+#[cfg(feature = "serde")]
+pub mod serde;
+pub mod sharded;
+pub mod strict;
 
 // --------------------------------------------------------------------------
 
@@ -27,10 +72,12 @@ pub mod nonzero;
 /// entries are stored in a [TotalHashMap] or [TotalBTreeMap].
 ///
 /// There could be multiple definitions of commonality for the same type. The basic implementation,
-/// [DefaultCommonality], is based on the [Default] trait.
+/// [DefaultCommonality], is based on the [Default] trait, [EmptyCommonality] treats empty
+/// collections as *common*, and [NoneCommonality] treats [None] as *common*.
 #[cfg_attr(
     feature = "num-traits",
-    doc = "Likewise, [ZeroCommonality] is based on the [num_traits::Zero] trait."
+    doc = "Likewise, [ZeroCommonality] is based on the [num_traits::Zero] trait, and \
+           [MinCommonality] and [MaxCommonality] on the [num_traits::Bounded] trait."
 )]
 pub trait Commonality<V> {
     /// The common value of type `V`.
@@ -39,6 +86,30 @@ pub trait Commonality<V> {
     /// Returns true if `value` is the common value of type `V`. `Self::is_common(Self::common())`
     /// must be true.
     fn is_common(value: &V) -> bool;
+
+    /// Returns the common value of type `V` if it is cheap and infallible to construct, or `None`
+    /// otherwise.
+    ///
+    /// Methods that only need the *common* value in order to return it, like
+    /// [TotalHashMap::remove_opt], call this instead of [`common()`](Self::common), and return
+    /// `None` in its place.
+    ///
+    /// Defaults to `Some(Self::common())` if the commonality is [CHEAP](Self::CHEAP), and `None`
+    /// otherwise.
+    fn try_common() -> Option<V> {
+        Self::CHEAP.then(Self::common)
+    }
+
+    /// Whether [`common()`](Self::common) is cheap to call, for example because it doesn't
+    /// allocate. Code that is generic over the commonality can use this to decide between
+    /// constructing the *common* value eagerly and avoiding its construction.
+    ///
+    /// In this crate, it determines the default of [`try_common()`](Self::try_common), and whether
+    /// [TotalHashMap::entry] and [TotalBTreeMap::entry] construct the *common* value for a vacant
+    /// entry up front, or only once the entry is modified.
+    ///
+    /// Defaults to false.
+    const CHEAP: bool = false;
 }
 
 /// A [commonality](Commonality) based on the [Default] trait.
@@ -69,10 +140,76 @@ impl<T: PartialEq + Default> Commonality<T> for DefaultCommonality {
     }
 }
 
+/// A [commonality](Commonality) whose *common* value can be constructed in a `const` context.
+///
+/// Maps using such a commonality can be created by a `const fn` (see [TotalBTreeMap::empty]),
+/// which allows them to be used in `static` and `const` items. Trait methods can't be `const fn`,
+/// so the *common* value is provided by an associated constant instead.
+pub trait ConstCommonality<V>: Commonality<V> {
+    /// The common value of type `V`. This must be equal to the value returned by
+    /// [`common()`](Commonality::common).
+    const COMMON: V;
+}
+
+/// A type with finitely many values, all of which can be listed.
+///
+/// Maps whose keys implement this trait can list the keys associated with the *common* value; see
+/// [TotalHashMap::common_keys] and [TotalBTreeMap::common_keys].
+pub trait Enumerable: Sized + 'static {
+    /// All values of the type, each listed exactly once.
+    const VALUES: &'static [Self];
+}
+
+impl Enumerable for bool {
+    const VALUES: &'static [Self] = &[false, true];
+}
+
+impl Enumerable for std::cmp::Ordering {
+    const VALUES: &'static [Self] = &[Self::Less, Self::Equal, Self::Greater];
+}
+
+/// Describes how the value associated with a key is represented in a [TotalHashMap] or
+/// [TotalBTreeMap].
+///
+/// This is returned by [TotalHashMap::raw_occupancy] and [TotalBTreeMap::raw_occupancy].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Occupancy {
+    /// The key is associated with an *uncommon* value, which is stored in the map.
+    UncommonStored,
+    /// The key is associated with the *common* value, which is implicit; nothing is stored for the
+    /// key.
+    CommonImplicit,
+}
+
+/// The error returned when constructing a [TotalHashMap] or [TotalBTreeMap] from a map `M` that
+/// contains entries with *common* values, by [TotalHashMap::try_from_hash_map] or
+/// [TotalBTreeMap::try_from_btree_map].
+///
+/// The error gives back the source map unchanged, so a failed conversion doesn't lose any data.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommonValuePresent<K, M> {
+    /// The keys of all entries whose values were *common*, in the source map's iteration order.
+    pub keys: Vec<K>,
+    /// The source map, including the entries with *common* values.
+    pub map: M,
+}
+
+impl<K, M> Display for CommonValuePresent<K, M> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "map contains {} common values", self.keys.len())
+    }
+}
+impl<K: Debug, M: Debug> Error for CommonValuePresent<K, M> {}
+
 struct PhantomPtr<T>(PhantomData<*const T>);
+impl<T> PhantomPtr<T> {
+    const fn new() -> Self {
+        Self(PhantomData)
+    }
+}
 impl<T> Default for PhantomPtr<T> {
     fn default() -> Self {
-        Self(PhantomData)
+        Self::new()
     }
 }
 impl<T> Debug for PhantomPtr<T> {
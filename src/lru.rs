@@ -0,0 +1,163 @@
+//! Provides [BoundedTotalHashMap], a [TotalHashMap] that stores a bounded number of *uncommon*
+//! entries by resetting the least recently used ones to the *common* value.
+
+use std::{
+    borrow::Borrow,
+    collections::{BTreeMap, HashMap},
+    fmt::{self, Debug, Formatter},
+    hash::Hash,
+};
+
+use crate::{Commonality, DefaultCommonality, TotalHashMap};
+
+// --------------------------------------------------------------------------
+
+/// A hash map in which every possible key has an associated value, and which stores at most a fixed
+/// number of *uncommon* entries.
+///
+/// When inserting an *uncommon* entry would exceed the map's capacity, the least recently used
+/// entry is evicted: it is reset to the *common* value, and passed to the eviction callback, if one
+/// has been set with [`on_evict()`](Self::on_evict). An entry is used when it is inserted, or read
+/// through [`get()`](Self::get); [`peek()`](Self::peek) reads an entry without using it.
+///
+/// This suits approximate sparse caches, in which a stale entry can safely revert to the *common*
+/// value. Each key is stored three times: in the map itself, and in two indices that track the
+/// order in which entries were used.
+pub struct BoundedTotalHashMap<K, V, C = DefaultCommonality> {
+    map: TotalHashMap<K, V, C>,
+    capacity: usize,
+    // The time of each stored key's last use, and the stored keys indexed by that time
+    last_used: HashMap<K, u64>,
+    by_last_use: BTreeMap<u64, K>,
+    clock: u64,
+    on_evict: Option<Box<dyn FnMut(K, V) + Send>>,
+}
+
+impl<K, V, C: Commonality<V>> BoundedTotalHashMap<K, V, C> {
+    /// Constructs a `BoundedTotalHashMap` that stores at most `capacity` *uncommon* entries, and in
+    /// which all keys are associated with the *common* value.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            map: TotalHashMap::new(),
+            capacity,
+            last_used: HashMap::new(),
+            by_last_use: BTreeMap::new(),
+            clock: 0,
+            on_evict: None,
+        }
+    }
+}
+
+impl<K, V, C> BoundedTotalHashMap<K, V, C> {
+    /// Sets a callback that is called with the key and value of each evicted entry, replacing any
+    /// previous callback. Entries reset to the *common* value by [`remove()`](Self::remove) or by
+    /// inserting the *common* value are not evicted.
+    pub fn on_evict(&mut self, f: impl FnMut(K, V) + Send + 'static) {
+        self.on_evict = Some(Box::new(f));
+    }
+    /// Returns the maximum number of *uncommon* entries the map stores.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    /// Returns the number of *uncommon* entries in the map.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+    /// Returns true if the map contains no *uncommon* entries.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+    /// Unwraps the underlying [TotalHashMap], discarding the order in which entries were used.
+    pub fn into_inner(self) -> TotalHashMap<K, V, C> {
+        self.map
+    }
+}
+
+// --------------------------------------------------------------------------
+// Element access
+
+impl<K: Eq + Hash + Clone, V, C: Commonality<V>> BoundedTotalHashMap<K, V, C> {
+    /// Returns a reference to the value associated with the given key, marking its entry as the
+    /// most recently used.
+    pub fn get<Q>(&mut self, key: &Q) -> &V
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        if let Some(time) = self.last_used.get_mut(key) {
+            let key = self.by_last_use.remove(time).expect("stored key has no use time");
+            self.clock += 1;
+            *time = self.clock;
+            self.by_last_use.insert(self.clock, key);
+        }
+        self.map.get(key)
+    }
+    /// Returns a reference to the value associated with the given key, without marking its entry
+    /// as used.
+    pub fn peek<Q>(&self, key: &Q) -> &V
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.map.get(key)
+    }
+    /// Returns true if the map contains an *uncommon* entry with the given key, without marking it
+    /// as used.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.map.contains_key(key)
+    }
+
+    /// Associates a key with a value in the map, and returns the value previously associated with
+    /// that key.
+    ///
+    /// If the value is *uncommon*, its entry becomes the most recently used, and if the map is then
+    /// over capacity, the least recently used entry is evicted.
+    pub fn insert(&mut self, key: K, value: V) -> V {
+        if C::is_common(&value) {
+            return self.remove(&key);
+        }
+        self.clock += 1;
+        if let Some(time) = self.last_used.insert(key.clone(), self.clock) {
+            self.by_last_use.remove(&time);
+        }
+        self.by_last_use.insert(self.clock, key.clone());
+        let old = self.map.insert(key, value);
+        while self.map.len() > self.capacity {
+            let (_, key) = self.by_last_use.pop_first().expect("stored key has no use time");
+            self.last_used.remove(&key);
+            let (key, value) = self.map.remove_entry(&key).expect("used key is not stored");
+            if let Some(on_evict) = &mut self.on_evict {
+                on_evict(key, value);
+            }
+        }
+        old
+    }
+    /// Associates a key with the *common* value in the map, and returns the value previously
+    /// associated with that key. This doesn't count as an eviction.
+    pub fn remove<Q>(&mut self, key: &Q) -> V
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        if let Some(time) = self.last_used.remove(key) {
+            self.by_last_use.remove(&time);
+        }
+        self.map.remove(key)
+    }
+}
+
+// --------------------------------------------------------------------------
+// Miscellaneous traits
+
+impl<K: Debug, V: Debug, C> Debug for BoundedTotalHashMap<K, V, C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BoundedTotalHashMap")
+            .field("capacity", &self.capacity)
+            .field("map", &self.map)
+            .finish()
+    }
+}
@@ -5,7 +5,7 @@
 
 use num_traits::Zero;
 
-use crate::{Commonality, TotalBTreeMap, TotalHashMap};
+use crate::{Commonality, ConstCommonality, TotalBTreeMap, TotalHashMap};
 
 /// A hash map that only stores entries with non-zero values. All other keys are presumed to be
 /// associated with the zero value.
@@ -15,6 +15,29 @@ pub type NonZeroHashMap<K, V> = TotalHashMap<K, V, ZeroCommonality>;
 /// associated with the zero value.
 pub type NonZeroBTreeMap<K, V> = TotalBTreeMap<K, V, ZeroCommonality>;
 
+/// A hash map from keys to `i64` values that only stores entries with non-zero values.
+pub type SparseI64Map<K> = NonZeroHashMap<K, i64>;
+/// A hash map from keys to `u64` values that only stores entries with non-zero values.
+pub type SparseU64Map<K> = NonZeroHashMap<K, u64>;
+/// A hash map from keys to `f64` values that only stores entries with non-zero values.
+pub type SparseF64Map<K> = NonZeroHashMap<K, f64>;
+
+/// An ordered map from keys to `i64` values that only stores entries with non-zero values.
+pub type SparseI64BTreeMap<K> = NonZeroBTreeMap<K, i64>;
+/// An ordered map from keys to `u64` values that only stores entries with non-zero values.
+pub type SparseU64BTreeMap<K> = NonZeroBTreeMap<K, u64>;
+/// An ordered map from keys to `f64` values that only stores entries with non-zero values.
+pub type SparseF64BTreeMap<K> = NonZeroBTreeMap<K, f64>;
+
+/// Constructs a sparse vector: an ordered map from indices to the given values, which only stores
+/// the non-zero values.
+///
+/// The value at each index of the returned map is the value at the same position of `values`, and
+/// all indices past the end of `values` are associated with the zero value.
+pub fn sparse_vec<V: Zero>(values: impl IntoIterator<Item = V>) -> NonZeroBTreeMap<usize, V> {
+    values.into_iter().enumerate().collect()
+}
+
 /// A [commonality](Commonality) based on the [Zero] trait.
 ///
 /// A [TotalHashMap] or [TotalBTreeMap] using this commonality only stores entries with nonzero
@@ -22,6 +45,8 @@ pub type NonZeroBTreeMap<K, V> = TotalBTreeMap<K, V, ZeroCommonality>;
 pub struct ZeroCommonality(());
 
 impl<T: Zero> Commonality<T> for ZeroCommonality {
+    const CHEAP: bool = true;
+
     fn common() -> T {
         T::zero()
     }
@@ -29,3 +54,12 @@ impl<T: Zero> Commonality<T> for ZeroCommonality {
         value.is_zero()
     }
 }
+
+macro_rules! impl_const_zero {
+    ($($t:ty)*) => {$(
+        impl ConstCommonality<$t> for ZeroCommonality {
+            const COMMON: $t = 0;
+        }
+    )*};
+}
+impl_const_zero!(i8 i16 i32 i64 i128 isize u8 u16 u32 u64 u128 usize);
@@ -0,0 +1,34 @@
+//! Provides [NoneCommonality], a [commonality](Commonality) for [Option] under which [None] is the
+//! *common* value, and maps that use it.
+
+use crate::{Commonality, ConstCommonality, TotalBTreeMap, TotalHashMap};
+
+/// A hash map that only stores entries with [Some] values. All other keys are presumed to be
+/// associated with [None].
+pub type SparseHashMap<K, T> = TotalHashMap<K, Option<T>, NoneCommonality>;
+
+/// An ordered map that only stores entries with [Some] values. All other keys are presumed to be
+/// associated with [None].
+pub type SparseBTreeMap<K, T> = TotalBTreeMap<K, Option<T>, NoneCommonality>;
+
+/// A [commonality](Commonality) for [Option], under which [None] is the *common* value.
+///
+/// A [TotalHashMap] or [TotalBTreeMap] using this commonality only stores entries with [Some]
+/// values. Unlike [DefaultCommonality](crate::DefaultCommonality), this doesn't require the
+/// contained type to implement [PartialEq].
+pub struct NoneCommonality(());
+
+impl<T> Commonality<Option<T>> for NoneCommonality {
+    const CHEAP: bool = true;
+
+    fn common() -> Option<T> {
+        None
+    }
+    fn is_common(value: &Option<T>) -> bool {
+        value.is_none()
+    }
+}
+
+impl<T> ConstCommonality<Option<T>> for NoneCommonality {
+    const COMMON: Option<T> = None;
+}
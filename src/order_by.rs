@@ -0,0 +1,100 @@
+//! Provides [OrderBy], a key wrapper that orders keys using a custom [Comparator] rather than their
+//! [Ord] implementation.
+//!
+//! A [BTreeMap](std::collections::BTreeMap), and therefore a [TotalBTreeMap], can't be given a
+//! comparator; it always orders keys using [Ord]. Instead, a map can be keyed by `OrderBy<K, O>`,
+//! which orders keys of type `K` using the comparator `O`. [TotalBTreeMapBy] is an alias for such a
+//! map.
+//!
+//! Every ordered operation of the map, including lookups like [TotalBTreeMap::get] and range
+//! queries like [TotalBTreeMap::lower_bound], then uses the comparator. Lookups take a
+//! `&OrderBy<K, O>`, which can be obtained from a `&K` without cloning the key by calling
+//! [OrderBy::from_ref]. Lookups by other borrowed forms of `K` (for example, looking up a `String`
+//! key by `&str`) are not supported.
+
+use std::{
+    cmp::Ordering,
+    fmt::{self, Debug, Formatter},
+    ops::Deref,
+};
+
+use crate::{DefaultCommonality, PhantomPtr, TotalBTreeMap};
+
+/// An ordered map whose keys are ordered by the [Comparator] `O`. See the
+/// [module documentation](self) for more information.
+pub type TotalBTreeMapBy<K, V, O, C = DefaultCommonality> = TotalBTreeMap<OrderBy<K, O>, V, C>;
+
+/// Defines a total order on values of type `T`.
+///
+/// The order must satisfy the same requirements as an [Ord] implementation.
+pub trait Comparator<T> {
+    /// Compares two values.
+    fn compare(lhs: &T, rhs: &T) -> Ordering;
+}
+
+/// A key of type `K`, ordered (and compared for equality) using the [Comparator] `O`. See the
+/// [module documentation](self) for more information.
+#[repr(transparent)]
+pub struct OrderBy<K, O> {
+    _order: PhantomPtr<O>,
+    key: K,
+}
+
+impl<K, O> OrderBy<K, O> {
+    /// Wraps a key.
+    pub fn new(key: K) -> Self {
+        Self { _order: PhantomPtr::default(), key }
+    }
+    /// Wraps a reference to a key, without cloning it.
+    pub fn from_ref(key: &K) -> &Self {
+        // SAFETY: OrderBy is #[repr(transparent)], and its only other field is zero-sized, so it
+        // has the same layout as K
+        unsafe { &*(key as *const K as *const Self) }
+    }
+    /// Unwraps the key.
+    pub fn into_inner(self) -> K {
+        self.key
+    }
+}
+
+impl<K, O> Deref for OrderBy<K, O> {
+    type Target = K;
+    fn deref(&self) -> &Self::Target {
+        &self.key
+    }
+}
+
+impl<K, O> From<K> for OrderBy<K, O> {
+    fn from(key: K) -> Self {
+        Self::new(key)
+    }
+}
+
+impl<K: Clone, O> Clone for OrderBy<K, O> {
+    fn clone(&self) -> Self {
+        Self::new(self.key.clone())
+    }
+}
+
+impl<K, O: Comparator<K>> PartialEq for OrderBy<K, O> {
+    fn eq(&self, other: &Self) -> bool {
+        O::compare(&self.key, &other.key) == Ordering::Equal
+    }
+}
+impl<K, O: Comparator<K>> Eq for OrderBy<K, O> {}
+impl<K, O: Comparator<K>> PartialOrd for OrderBy<K, O> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl<K, O: Comparator<K>> Ord for OrderBy<K, O> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        O::compare(&self.key, &other.key)
+    }
+}
+
+impl<K: Debug, O> Debug for OrderBy<K, O> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.key.fmt(f)
+    }
+}
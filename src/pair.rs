@@ -0,0 +1,29 @@
+//! Provides [PairCommonality], a [commonality](Commonality) for pairs that combines a commonality
+//! for each component.
+
+use crate::{Commonality, ConstCommonality, PhantomPtr};
+
+/// A [commonality](Commonality) for pairs `(A, B)`, under which a pair is *common* if both of its
+/// components are *common*: `A` under the commonality `CA`, and `B` under the commonality `CB`.
+///
+/// A [TotalHashMap](crate::TotalHashMap) or [TotalBTreeMap](crate::TotalBTreeMap) using this
+/// commonality only stores entries in which at least one component of the value is *uncommon*.
+/// Larger product types can be handled by nesting pairs.
+pub struct PairCommonality<CA, CB>(PhantomPtr<(CA, CB)>);
+
+impl<A, B, CA: Commonality<A>, CB: Commonality<B>> Commonality<(A, B)> for PairCommonality<CA, CB> {
+    const CHEAP: bool = CA::CHEAP && CB::CHEAP;
+
+    fn common() -> (A, B) {
+        (CA::common(), CB::common())
+    }
+    fn is_common(value: &(A, B)) -> bool {
+        CA::is_common(&value.0) && CB::is_common(&value.1)
+    }
+}
+
+impl<A, B, CA: ConstCommonality<A>, CB: ConstCommonality<B>> ConstCommonality<(A, B)>
+    for PairCommonality<CA, CB>
+{
+    const COMMON: (A, B) = (CA::COMMON, CB::COMMON);
+}
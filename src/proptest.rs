@@ -0,0 +1,119 @@
+//! Provides [proptest] support for [TotalHashMap] and [TotalBTreeMap].
+//!
+//! Both maps implement [Arbitrary]. The generated maps uphold the usual invariant: values are drawn
+//! from the value type's strategy with *common* values filtered out, so every generated entry is
+//! *uncommon*, and the entries are inserted into the map with [TotalHashMap::insert] or
+//! [TotalBTreeMap::insert]. The [Arbitrary] parameters are a [SizeRange] for the number of
+//! *uncommon* entries, and the parameters for the key and value types.
+//!
+//! To generate maps from custom key and value strategies, use [total_hash_map] or
+//! [total_btree_map].
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::{BuildHasher, Hash},
+};
+
+use proptest::{
+    arbitrary::{any_with, Arbitrary},
+    collection::{self, BTreeMapStrategy, HashMapStrategy, SizeRange},
+    strategy::{Filter, Map, Strategy},
+};
+
+use crate::{Commonality, TotalBTreeMap, TotalHashMap};
+
+// --------------------------------------------------------------------------
+// Strategies
+
+/// A strategy that generates a [TotalHashMap]. This is created by [total_hash_map].
+pub type TotalHashMapStrategy<K, V, C, S> = Map<
+    HashMapStrategy<K, Uncommon<V>>,
+    fn(
+        HashMap<<K as Strategy>::Value, <V as Strategy>::Value>,
+    ) -> TotalHashMap<<K as Strategy>::Value, <V as Strategy>::Value, C, S>,
+>;
+
+/// A strategy that generates a [TotalBTreeMap]. This is created by [total_btree_map].
+pub type TotalBTreeMapStrategy<K, V, C> = Map<
+    BTreeMapStrategy<K, Uncommon<V>>,
+    fn(
+        BTreeMap<<K as Strategy>::Value, <V as Strategy>::Value>,
+    ) -> TotalBTreeMap<<K as Strategy>::Value, <V as Strategy>::Value, C>,
+>;
+
+/// A strategy that generates only *uncommon* values.
+pub type Uncommon<V> = Filter<V, fn(&<V as Strategy>::Value) -> bool>;
+
+/// Returns a strategy that generates a [TotalHashMap] with a number of *uncommon* entries in the
+/// given range, with keys drawn from `key` and values drawn from `value`. *Common* values drawn
+/// from `value` are rejected.
+pub fn total_hash_map<K, V, C, S>(
+    key: K,
+    value: V,
+    size: impl Into<SizeRange>,
+) -> TotalHashMapStrategy<K, V, C, S>
+where
+    K: Strategy,
+    K::Value: Hash + Eq,
+    V: Strategy,
+    C: Commonality<V::Value>,
+    S: BuildHasher + Default,
+{
+    collection::hash_map(key, uncommon::<V, C>(value), size)
+        .prop_map(|entries| entries.into_iter().collect())
+}
+
+/// Returns a strategy that generates a [TotalBTreeMap] with a number of *uncommon* entries in the
+/// given range, with keys drawn from `key` and values drawn from `value`. *Common* values drawn
+/// from `value` are rejected.
+pub fn total_btree_map<K, V, C>(
+    key: K,
+    value: V,
+    size: impl Into<SizeRange>,
+) -> TotalBTreeMapStrategy<K, V, C>
+where
+    K: Strategy,
+    K::Value: Ord,
+    V: Strategy,
+    C: Commonality<V::Value>,
+{
+    collection::btree_map(key, uncommon::<V, C>(value), size)
+        .prop_map(|entries| entries.into_iter().collect())
+}
+
+// --------------------------------------------------------------------------
+// Arbitrary maps
+
+impl<K, V, C, S> Arbitrary for TotalHashMap<K, V, C, S>
+where
+    K: Arbitrary + Hash + Eq,
+    V: Arbitrary,
+    C: Commonality<V>,
+    S: BuildHasher + Default,
+{
+    type Parameters = (SizeRange, K::Parameters, V::Parameters);
+    type Strategy = TotalHashMapStrategy<K::Strategy, V::Strategy, C, S>;
+    fn arbitrary_with((size, key, value): Self::Parameters) -> Self::Strategy {
+        total_hash_map(any_with::<K>(key), any_with::<V>(value), size)
+    }
+}
+
+impl<K, V, C> Arbitrary for TotalBTreeMap<K, V, C>
+where
+    K: Arbitrary + Ord,
+    V: Arbitrary,
+    C: Commonality<V>,
+{
+    type Parameters = (SizeRange, K::Parameters, V::Parameters);
+    type Strategy = TotalBTreeMapStrategy<K::Strategy, V::Strategy, C>;
+    fn arbitrary_with((size, key, value): Self::Parameters) -> Self::Strategy {
+        total_btree_map(any_with::<K>(key), any_with::<V>(value), size)
+    }
+}
+
+// --------------------------------------------------------------------------
+// Implementation details
+
+fn uncommon<V: Strategy, C: Commonality<V::Value>>(value: V) -> Uncommon<V> {
+    value.prop_filter("common value", |value| !C::is_common(value))
+}
@@ -0,0 +1,47 @@
+//! Provides [quickcheck] support for [TotalHashMap] and [TotalBTreeMap].
+//!
+//! Both maps implement [Arbitrary]. Maps are generated from arbitrary vectors of entries, which are
+//! inserted into the map so that entries with *common* values are discarded. Maps are shrunk by
+//! shrinking the vector of their *uncommon* entries, which removes entries and shrinks individual
+//! keys and values; any values that shrink to the *common* value are discarded in turn.
+
+use std::hash::{BuildHasher, Hash};
+
+use quickcheck::{Arbitrary, Gen};
+
+use crate::{Commonality, TotalBTreeMap, TotalHashMap};
+
+impl<K, V, C, S> Arbitrary for TotalHashMap<K, V, C, S>
+where
+    K: Arbitrary + Eq + Hash,
+    V: Arbitrary,
+    C: Commonality<V> + 'static,
+    S: BuildHasher + Default + Clone + 'static,
+{
+    fn arbitrary(g: &mut Gen) -> Self {
+        let entries: Vec<(K, V)> = Arbitrary::arbitrary(g);
+        entries.into_iter().collect()
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let entries: Vec<(K, V)> = self.clone().into_iter().collect();
+        Box::new(entries.shrink().map(|entries| entries.into_iter().collect()))
+    }
+}
+
+impl<K, V, C> Arbitrary for TotalBTreeMap<K, V, C>
+where
+    K: Arbitrary + Ord,
+    V: Arbitrary,
+    C: Commonality<V> + 'static,
+{
+    fn arbitrary(g: &mut Gen) -> Self {
+        let entries: Vec<(K, V)> = Arbitrary::arbitrary(g);
+        entries.into_iter().collect()
+    }
+
+    fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+        let entries: Vec<(K, V)> = self.clone().into_iter().collect();
+        Box::new(entries.shrink().map(|entries| entries.into_iter().collect()))
+    }
+}
@@ -0,0 +1,244 @@
+//! Provides [rkyv] support for [TotalHashMap] and [TotalBTreeMap].
+//!
+//! Both maps are archived as their underlying map of *uncommon* entries together with the *common*
+//! value, so that the archived maps can look up any key without being deserialized. When
+//! deserializing, the *common* value is taken from the map's [Commonality] rather than from the
+//! archive, and any archived entries with *common* values are discarded.
+
+use std::{
+    borrow::Borrow,
+    collections::{BTreeMap, HashMap},
+    fmt::{self, Debug, Formatter},
+    hash::{BuildHasher, Hash},
+};
+
+use rkyv::{
+    collections::{
+        btree_map::{ArchivedBTreeMap, BTreeMapResolver},
+        swiss_table::{ArchivedHashMap, HashMapResolver},
+    },
+    munge::munge,
+    rancor::Fallible,
+    Archive, Deserialize, Place, Portable, Serialize,
+};
+
+use crate::{Commonality, TotalBTreeMap, TotalHashMap};
+
+// --------------------------------------------------------------------------
+// Archived maps
+
+/// An archived [TotalHashMap].
+#[derive(Portable, rkyv::bytecheck::CheckBytes)]
+#[bytecheck(crate = rkyv::bytecheck)]
+#[repr(C)]
+pub struct ArchivedTotalHashMap<K, V> {
+    inner: ArchivedHashMap<K, V>,
+    common: V,
+}
+
+impl<K: Hash + Eq, V> ArchivedTotalHashMap<K, V> {
+    /// Returns a reference to the value associated with the given key.
+    pub fn get<Q>(&self, key: &Q) -> &V
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.inner.get(key).unwrap_or(&self.common)
+    }
+    /// Returns true if the map contains an *uncommon* entry with the given key.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.inner.contains_key(key)
+    }
+}
+
+impl<K, V> ArchivedTotalHashMap<K, V> {
+    /// Returns the number of *uncommon* entries in the map.
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+    /// Returns true if the map contains no *uncommon* entries.
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+    /// Returns the archived *common* value.
+    pub fn common(&self) -> &V {
+        &self.common
+    }
+    /// Returns a reference to the underlying [ArchivedHashMap], which contains only the *uncommon*
+    /// entries.
+    pub fn as_archived_hash_map(&self) -> &ArchivedHashMap<K, V> {
+        &self.inner
+    }
+}
+
+impl<K: Debug, V: Debug> Debug for ArchivedTotalHashMap<K, V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ArchivedTotalHashMap")
+            .field("common", &self.common)
+            .field("uncommon", &self.inner)
+            .finish()
+    }
+}
+
+/// An archived [TotalBTreeMap].
+#[derive(Portable, rkyv::bytecheck::CheckBytes)]
+#[bytecheck(crate = rkyv::bytecheck)]
+#[repr(C)]
+pub struct ArchivedTotalBTreeMap<K, V> {
+    inner: ArchivedBTreeMap<K, V>,
+    common: V,
+}
+
+impl<K: Ord, V> ArchivedTotalBTreeMap<K, V> {
+    /// Returns a reference to the value associated with the given key.
+    pub fn get<Q>(&self, key: &Q) -> &V
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.inner.get(key).unwrap_or(&self.common)
+    }
+    /// Returns true if the map contains an *uncommon* entry with the given key.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.inner.contains_key(key)
+    }
+}
+
+impl<K, V> ArchivedTotalBTreeMap<K, V> {
+    /// Returns the number of *uncommon* entries in the map.
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+    /// Returns true if the map contains no *uncommon* entries.
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+    /// Returns the archived *common* value.
+    pub fn common(&self) -> &V {
+        &self.common
+    }
+    /// Returns a reference to the underlying [ArchivedBTreeMap], which contains only the
+    /// *uncommon* entries.
+    pub fn as_archived_btree_map(&self) -> &ArchivedBTreeMap<K, V> {
+        &self.inner
+    }
+}
+
+impl<K: Debug, V: Debug> Debug for ArchivedTotalBTreeMap<K, V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ArchivedTotalBTreeMap")
+            .field("common", &self.common)
+            .field("uncommon", &self.inner)
+            .finish()
+    }
+}
+
+/// The resolver for an archived [TotalHashMap] or [TotalBTreeMap].
+pub struct TotalMapResolver<R, C> {
+    inner: R,
+    common: C,
+}
+
+// --------------------------------------------------------------------------
+// Archiving
+
+impl<K, V: Archive, C, S> Archive for TotalHashMap<K, V, C, S>
+where
+    K: Archive + Hash + Eq,
+    K::Archived: Hash + Eq,
+{
+    type Archived = ArchivedTotalHashMap<K::Archived, V::Archived>;
+    type Resolver = TotalMapResolver<HashMapResolver, V::Resolver>;
+
+    fn resolve(&self, resolver: Self::Resolver, out: Place<Self::Archived>) {
+        munge!(let ArchivedTotalHashMap { inner, common } = out);
+        self.inner.resolve(resolver.inner, inner);
+        self.common.resolve(resolver.common, common);
+    }
+}
+
+impl<K, V, C, S, Ser> Serialize<Ser> for TotalHashMap<K, V, C, S>
+where
+    K: Archive + Hash + Eq,
+    K::Archived: Hash + Eq,
+    V: Serialize<Ser>,
+    HashMap<K, V, S>: Serialize<Ser, Resolver = HashMapResolver>,
+    Ser: Fallible + ?Sized,
+{
+    fn serialize(&self, serializer: &mut Ser) -> Result<Self::Resolver, Ser::Error> {
+        let inner = self.inner.serialize(serializer)?;
+        let common = self.common.serialize(serializer)?;
+        Ok(TotalMapResolver { inner, common })
+    }
+}
+
+impl<K: Archive + Ord, V: Archive, C> Archive for TotalBTreeMap<K, V, C>
+where
+    K::Archived: Ord,
+{
+    type Archived = ArchivedTotalBTreeMap<K::Archived, V::Archived>;
+    type Resolver = TotalMapResolver<BTreeMapResolver, V::Resolver>;
+
+    fn resolve(&self, resolver: Self::Resolver, out: Place<Self::Archived>) {
+        munge!(let ArchivedTotalBTreeMap { inner, common } = out);
+        self.inner.resolve(resolver.inner, inner);
+        self.common.resolve(resolver.common, common);
+    }
+}
+
+impl<K, V, C, Ser> Serialize<Ser> for TotalBTreeMap<K, V, C>
+where
+    K: Archive + Ord,
+    K::Archived: Ord,
+    V: Serialize<Ser>,
+    BTreeMap<K, V>: Serialize<Ser, Resolver = BTreeMapResolver>,
+    Ser: Fallible + ?Sized,
+{
+    fn serialize(&self, serializer: &mut Ser) -> Result<Self::Resolver, Ser::Error> {
+        let inner = self.inner.serialize(serializer)?;
+        let common = self.common.serialize(serializer)?;
+        Ok(TotalMapResolver { inner, common })
+    }
+}
+
+// --------------------------------------------------------------------------
+// Deserialization
+
+impl<K, V, C, S, De> Deserialize<TotalHashMap<K, V, C, S>, De>
+    for ArchivedTotalHashMap<K::Archived, V::Archived>
+where
+    K: Archive + Hash + Eq,
+    K::Archived: Deserialize<K, De> + Hash + Eq,
+    V: Archive,
+    V::Archived: Deserialize<V, De>,
+    C: Commonality<V>,
+    S: BuildHasher + Default,
+    De: Fallible + ?Sized,
+{
+    fn deserialize(&self, deserializer: &mut De) -> Result<TotalHashMap<K, V, C, S>, De::Error> {
+        Ok(TotalHashMap::from(self.inner.deserialize(deserializer)?))
+    }
+}
+
+impl<K, V, C, De> Deserialize<TotalBTreeMap<K, V, C>, De>
+    for ArchivedTotalBTreeMap<K::Archived, V::Archived>
+where
+    K: Archive + Ord,
+    K::Archived: Deserialize<K, De> + Ord,
+    V: Archive,
+    V::Archived: Deserialize<V, De>,
+    C: Commonality<V>,
+    De: Fallible + ?Sized,
+{
+    fn deserialize(&self, deserializer: &mut De) -> Result<TotalBTreeMap<K, V, C>, De::Error> {
+        Ok(TotalBTreeMap::from(self.inner.deserialize(deserializer)?))
+    }
+}
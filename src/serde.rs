@@ -0,0 +1,695 @@
+//! Provides [serde] support for [TotalHashMap] and [TotalBTreeMap].
+//!
+//! Both maps are serialized as a map containing only their *uncommon* entries. When deserializing,
+//! entries with *common* values are discarded, so that the resulting map upholds the usual
+//! invariant. Alternatively, entries with *common* values can be rejected as malformed input, using
+//! [deserialize_strict] or [deserialize_strict_named].
+//!
+//! The [Deserialize] implementations accept maps of any size. When deserializing untrusted input,
+//! use [bounded] instead to limit the number of entries that will be stored.
+//!
+//! A [TotalBTreeMap] keyed by `usize` can also be serialized as a dense array, in which every index
+//! in a range is present, using [as_dense_array] and [from_dense_array].
+//!
+//! Maps whose keys aren't strings can be serialized as a sequence of key-value pairs instead, which
+//! suits formats such as JSON that only allow string keys, using the [as_pairs] module.
+//!
+//! The plain serialized form doesn't record the *common* value, so a document can be silently
+//! reinterpreted by a map with a different [Commonality]. Wrap the map in [WithCommon] to include
+//! the *common* value and check it when deserializing.
+
+use std::{
+    fmt::{self, Display, Formatter},
+    hash::{BuildHasher, Hash},
+};
+
+use serde::{
+    de::{DeserializeSeed, Error, IgnoredAny, MapAccess, SeqAccess, Visitor},
+    ser::{self, SerializeSeq, SerializeTuple},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::{Commonality, PhantomPtr, TotalBTreeMap, TotalHashMap};
+
+// --------------------------------------------------------------------------
+// Serialization
+
+impl<K: Serialize, V: Serialize, C, S> Serialize for TotalHashMap<K, V, C, S> {
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        serializer.collect_map(self.iter())
+    }
+}
+
+impl<K: Serialize, V: Serialize, C> Serialize for TotalBTreeMap<K, V, C> {
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        serializer.collect_map(self.iter())
+    }
+}
+
+// --------------------------------------------------------------------------
+// Deserialization
+
+impl<'de, K, V, C, S> Deserialize<'de> for TotalHashMap<K, V, C, S>
+where
+    K: Deserialize<'de> + Eq + Hash,
+    V: Deserialize<'de>,
+    C: Commonality<V>,
+    S: BuildHasher + Default,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(MapVisitor::new(usize::MAX))
+    }
+}
+
+impl<'de, K, V, C> Deserialize<'de> for TotalBTreeMap<K, V, C>
+where
+    K: Deserialize<'de> + Ord,
+    V: Deserialize<'de>,
+    C: Commonality<V>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(MapVisitor::new(usize::MAX))
+    }
+}
+
+impl<K: Eq + Hash, V, C: Commonality<V>, S: BuildHasher + Default> TotalHashMap<K, V, C, S> {
+    /// Deserializes a map and inserts its entries into this map, replacing the values of any keys
+    /// that are already present. Entries with *common* values reset their keys to the *common*
+    /// value.
+    ///
+    /// If deserialization fails, the entries deserialized before the error remain in this map.
+    pub fn deserialize_into<'de, D>(&mut self, deserializer: D) -> Result<(), D::Error>
+    where
+        K: Deserialize<'de>,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(ExtendVisitor { map: self, max_len: usize::MAX })
+    }
+}
+
+impl<K: Ord, V, C: Commonality<V>> TotalBTreeMap<K, V, C> {
+    /// Deserializes a map and inserts its entries into this map, replacing the values of any keys
+    /// that are already present. Entries with *common* values reset their keys to the *common*
+    /// value.
+    ///
+    /// If deserialization fails, the entries deserialized before the error remain in this map.
+    pub fn deserialize_into<'de, D>(&mut self, deserializer: D) -> Result<(), D::Error>
+    where
+        K: Deserialize<'de>,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(ExtendVisitor { map: self, max_len: usize::MAX })
+    }
+}
+
+/// Returns a [DeserializeSeed] that deserializes a [TotalHashMap] or [TotalBTreeMap] of type `M`,
+/// failing if the map would store more than `max_len` *uncommon* entries.
+///
+/// Entries are counted as they are deserialized, and deserialization stops as soon as the limit is
+/// exceeded. Entries with *common* values are discarded and don't count towards the limit.
+pub fn bounded<M>(max_len: usize) -> Bounded<M> {
+    Bounded { max_len, _map: PhantomPtr::default() }
+}
+
+/// A [DeserializeSeed] that limits the number of entries stored in a deserialized map. This is
+/// created by [bounded].
+pub struct Bounded<M> {
+    max_len: usize,
+    _map: PhantomPtr<M>,
+}
+
+impl<'de, K, V, C, S> DeserializeSeed<'de> for Bounded<TotalHashMap<K, V, C, S>>
+where
+    K: Deserialize<'de> + Eq + Hash,
+    V: Deserialize<'de>,
+    C: Commonality<V>,
+    S: BuildHasher + Default,
+{
+    type Value = TotalHashMap<K, V, C, S>;
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(MapVisitor::new(self.max_len))
+    }
+}
+
+impl<'de, K, V, C> DeserializeSeed<'de> for Bounded<TotalBTreeMap<K, V, C>>
+where
+    K: Deserialize<'de> + Ord,
+    V: Deserialize<'de>,
+    C: Commonality<V>,
+{
+    type Value = TotalBTreeMap<K, V, C>;
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(MapVisitor::new(self.max_len))
+    }
+}
+
+// --------------------------------------------------------------------------
+// Strict deserialization
+
+/// Deserializes a [TotalHashMap] or [TotalBTreeMap], failing if the serialized map contains an
+/// entry with a *common* value, rather than discarding it. Use [deserialize_strict_named] to name
+/// the offending key in the error message.
+///
+/// This has the signature expected by `#[serde(deserialize_with = "...")]`.
+pub fn deserialize_strict<'de, M, D>(deserializer: D) -> Result<M, D::Error>
+where
+    Strict<M>: DeserializeSeed<'de, Value = M>,
+    D: Deserializer<'de>,
+{
+    Strict(PhantomPtr::default()).deserialize(deserializer)
+}
+
+/// Like [deserialize_strict], but the error message names the offending key, which must therefore
+/// implement [Display].
+///
+/// This has the signature expected by `#[serde(deserialize_with = "...")]`.
+pub fn deserialize_strict_named<'de, M, D>(deserializer: D) -> Result<M, D::Error>
+where
+    StrictNamed<M>: DeserializeSeed<'de, Value = M>,
+    D: Deserializer<'de>,
+{
+    StrictNamed(PhantomPtr::default()).deserialize(deserializer)
+}
+
+/// A [DeserializeSeed] that rejects entries with *common* values in a deserialized map. This is
+/// used by [deserialize_strict].
+pub struct Strict<M>(PhantomPtr<M>);
+
+/// A [DeserializeSeed] that rejects entries with *common* values in a deserialized map, naming the
+/// offending key. This is used by [deserialize_strict_named].
+pub struct StrictNamed<M>(PhantomPtr<M>);
+
+impl<'de, K, V, C, S> DeserializeSeed<'de> for Strict<TotalHashMap<K, V, C, S>>
+where
+    K: Deserialize<'de> + Eq + Hash,
+    V: Deserialize<'de>,
+    C: Commonality<V>,
+    S: BuildHasher + Default,
+{
+    type Value = TotalHashMap<K, V, C, S>;
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(StrictVisitor::new(|_| None))
+    }
+}
+
+impl<'de, K, V, C> DeserializeSeed<'de> for Strict<TotalBTreeMap<K, V, C>>
+where
+    K: Deserialize<'de> + Ord,
+    V: Deserialize<'de>,
+    C: Commonality<V>,
+{
+    type Value = TotalBTreeMap<K, V, C>;
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(StrictVisitor::new(|_| None))
+    }
+}
+
+impl<'de, K, V, C, S> DeserializeSeed<'de> for StrictNamed<TotalHashMap<K, V, C, S>>
+where
+    K: Deserialize<'de> + Eq + Hash + Display,
+    V: Deserialize<'de>,
+    C: Commonality<V>,
+    S: BuildHasher + Default,
+{
+    type Value = TotalHashMap<K, V, C, S>;
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(StrictVisitor::new(|key: &K| Some(key.to_string())))
+    }
+}
+
+impl<'de, K, V, C> DeserializeSeed<'de> for StrictNamed<TotalBTreeMap<K, V, C>>
+where
+    K: Deserialize<'de> + Ord + Display,
+    V: Deserialize<'de>,
+    C: Commonality<V>,
+{
+    type Value = TotalBTreeMap<K, V, C>;
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(StrictVisitor::new(|key: &K| Some(key.to_string())))
+    }
+}
+
+// --------------------------------------------------------------------------
+// JSON conversion
+
+#[cfg(feature = "serde_json")]
+impl<K: Serialize, V: Serialize, C, S> TotalHashMap<K, V, C, S> {
+    /// Converts the *uncommon* entries of the map into a JSON object.
+    ///
+    /// JSON object keys are strings, so each key must serialize as a string, or as a value that
+    /// [serde_json] converts into one (an integer, a `char`, or a `bool`). Returns an error if a
+    /// key serializes as any other kind of value, or if a value fails to serialize.
+    pub fn to_json_object(&self) -> Result<serde_json::Value, serde_json::Error> {
+        serde_json::to_value(self)
+    }
+}
+
+#[cfg(feature = "serde_json")]
+impl<K: Serialize, V: Serialize, C> TotalBTreeMap<K, V, C> {
+    /// Converts the *uncommon* entries of the map into a JSON object.
+    ///
+    /// JSON object keys are strings, so each key must serialize as a string, or as a value that
+    /// [serde_json] converts into one (an integer, a `char`, or a `bool`). Returns an error if a
+    /// key serializes as any other kind of value, or if a value fails to serialize.
+    pub fn to_json_object(&self) -> Result<serde_json::Value, serde_json::Error> {
+        serde_json::to_value(self)
+    }
+}
+
+// --------------------------------------------------------------------------
+// Recording the common value
+
+/// A wrapper that serializes a [TotalHashMap] or [TotalBTreeMap] together with its *common* value,
+/// as a pair of the *common* value and the usual map of *uncommon* entries.
+///
+/// When deserializing, the recorded *common* value must be *common* under the map's [Commonality],
+/// or deserialization fails. This guards against reading a document under the wrong
+/// commonality, which would silently change the value associated with every absent key.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WithCommon<M>(pub M);
+
+impl<K: Serialize, V: Serialize, C, S> Serialize for WithCommon<TotalHashMap<K, V, C, S>> {
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        serialize_with_common(&self.0.common, &self.0, serializer)
+    }
+}
+
+impl<K: Serialize, V: Serialize, C> Serialize for WithCommon<TotalBTreeMap<K, V, C>> {
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        serialize_with_common(&self.0.common, &self.0, serializer)
+    }
+}
+
+impl<'de, K, V, C, S> Deserialize<'de> for WithCommon<TotalHashMap<K, V, C, S>>
+where
+    K: Deserialize<'de> + Eq + Hash,
+    V: Deserialize<'de>,
+    C: Commonality<V>,
+    S: BuildHasher + Default,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_tuple(2, WithCommonVisitor(PhantomPtr::default()))
+    }
+}
+
+impl<'de, K, V, C> Deserialize<'de> for WithCommon<TotalBTreeMap<K, V, C>>
+where
+    K: Deserialize<'de> + Ord,
+    V: Deserialize<'de>,
+    C: Commonality<V>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_tuple(2, WithCommonVisitor(PhantomPtr::default()))
+    }
+}
+
+// --------------------------------------------------------------------------
+// Dense arrays
+
+/// Returns a [Serialize] adapter that serializes `map` as a dense array of length `len`, in which
+/// the element at each index is the value associated with that key.
+///
+/// Serialization fails if the map contains an *uncommon* entry whose key is not less than `len`.
+/// Use [from_dense_array] to deserialize the array back into a map.
+pub fn as_dense_array<V, C>(map: &TotalBTreeMap<usize, V, C>, len: usize) -> DenseArray<'_, V, C> {
+    DenseArray { map, len }
+}
+
+/// A [Serialize] adapter that serializes a [TotalBTreeMap] as a dense array. This is created by
+/// [as_dense_array].
+pub struct DenseArray<'a, V, C> {
+    map: &'a TotalBTreeMap<usize, V, C>,
+    len: usize,
+}
+
+impl<V: Serialize, C> Serialize for DenseArray<'_, V, C> {
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        if let Some((&key, _)) = self.map.inner.last_key_value() {
+            if key >= self.len {
+                return Err(ser::Error::custom(format_args!(
+                    "key {key} is out of range for a dense array of length {}",
+                    self.len
+                )));
+            }
+        }
+        let mut seq = serializer.serialize_seq(Some(self.len))?;
+        let mut entries = self.map.iter().peekable();
+        for index in 0..self.len {
+            match entries.next_if(|(&key, _)| key == index) {
+                Some((_, value)) => seq.serialize_element(value)?,
+                None => seq.serialize_element(&self.map.common)?,
+            }
+        }
+        seq.end()
+    }
+}
+
+/// Returns a [DeserializeSeed] that deserializes a dense array of length `len`, as serialized by
+/// [as_dense_array], into a [TotalBTreeMap] keyed by index. Elements with *common* values are
+/// discarded.
+///
+/// Deserialization fails if the array does not have exactly `len` elements.
+pub fn from_dense_array<V, C>(len: usize) -> FromDenseArray<V, C> {
+    FromDenseArray { len, _map: PhantomPtr::default() }
+}
+
+/// A [DeserializeSeed] that deserializes a dense array into a [TotalBTreeMap]. This is created by
+/// [from_dense_array].
+pub struct FromDenseArray<V, C> {
+    len: usize,
+    _map: PhantomPtr<TotalBTreeMap<usize, V, C>>,
+}
+
+impl<'de, V: Deserialize<'de>, C: Commonality<V>> DeserializeSeed<'de> for FromDenseArray<V, C> {
+    type Value = TotalBTreeMap<usize, V, C>;
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, V: Deserialize<'de>, C: Commonality<V>> Visitor<'de> for FromDenseArray<V, C> {
+    type Value = TotalBTreeMap<usize, V, C>;
+
+    fn expecting(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "an array of length {}", self.len)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
+        let mut map = TotalBTreeMap::new();
+        for index in 0..self.len {
+            match access.next_element()? {
+                Some(value) => map.insert(index, value),
+                None => return Err(A::Error::invalid_length(index, &self)),
+            };
+        }
+        if access.next_element::<IgnoredAny>()?.is_some() {
+            return Err(A::Error::invalid_length(self.len + 1, &self));
+        }
+        Ok(map)
+    }
+}
+
+// --------------------------------------------------------------------------
+// Sequences of pairs
+
+pub mod as_pairs {
+    //! Serializes a [TotalHashMap] or [TotalBTreeMap] as a sequence of `(key, value)` pairs
+    //! containing only its *uncommon* entries, rather than as a map. When deserializing, pairs with
+    //! *common* values are discarded.
+    //!
+    //! This module can be used with `#[serde(with = "total_maps::serde::as_pairs")]`.
+
+    use std::{
+        fmt::{self, Formatter},
+        hash::{BuildHasher, Hash},
+    };
+
+    use serde::{
+        de::{DeserializeSeed, SeqAccess, Visitor},
+        Deserialize, Deserializer, Serialize, Serializer,
+    };
+
+    use super::TotalMap;
+    use crate::{Commonality, PhantomPtr, TotalBTreeMap, TotalHashMap};
+
+    /// Serializes `map` as a sequence of pairs.
+    pub fn serialize<'a, M, Ser>(map: &'a M, serializer: Ser) -> Result<Ser::Ok, Ser::Error>
+    where
+        Pairs<'a, M>: Serialize,
+        Ser: Serializer,
+    {
+        Pairs(map).serialize(serializer)
+    }
+
+    /// Deserializes a map from a sequence of pairs, discarding pairs with *common* values.
+    pub fn deserialize<'de, M, D>(deserializer: D) -> Result<M, D::Error>
+    where
+        FromPairs<M>: DeserializeSeed<'de, Value = M>,
+        D: Deserializer<'de>,
+    {
+        FromPairs(PhantomPtr::default()).deserialize(deserializer)
+    }
+
+    /// A [Serialize] adapter that serializes a [TotalHashMap] or [TotalBTreeMap] as a sequence of
+    /// pairs.
+    #[derive(Clone, Copy, Debug)]
+    pub struct Pairs<'a, M>(pub &'a M);
+
+    impl<K: Serialize, V: Serialize, C, S> Serialize for Pairs<'_, TotalHashMap<K, V, C, S>> {
+        fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+            serializer.collect_seq(self.0.iter())
+        }
+    }
+
+    impl<K: Serialize, V: Serialize, C> Serialize for Pairs<'_, TotalBTreeMap<K, V, C>> {
+        fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+            serializer.collect_seq(self.0.iter())
+        }
+    }
+
+    /// A [DeserializeSeed] that deserializes a [TotalHashMap] or [TotalBTreeMap] from a sequence of
+    /// pairs. This is used by [deserialize].
+    pub struct FromPairs<M>(PhantomPtr<M>);
+
+    impl<'de, K, V, C, S> DeserializeSeed<'de> for FromPairs<TotalHashMap<K, V, C, S>>
+    where
+        K: Deserialize<'de> + Eq + Hash,
+        V: Deserialize<'de>,
+        C: Commonality<V>,
+        S: BuildHasher + Default,
+    {
+        type Value = TotalHashMap<K, V, C, S>;
+        fn deserialize<D: Deserializer<'de>>(
+            self,
+            deserializer: D,
+        ) -> Result<Self::Value, D::Error> {
+            deserializer.deserialize_seq(PairsVisitor(self.0))
+        }
+    }
+
+    impl<'de, K, V, C> DeserializeSeed<'de> for FromPairs<TotalBTreeMap<K, V, C>>
+    where
+        K: Deserialize<'de> + Ord,
+        V: Deserialize<'de>,
+        C: Commonality<V>,
+    {
+        type Value = TotalBTreeMap<K, V, C>;
+        fn deserialize<D: Deserializer<'de>>(
+            self,
+            deserializer: D,
+        ) -> Result<Self::Value, D::Error> {
+            deserializer.deserialize_seq(PairsVisitor(self.0))
+        }
+    }
+
+    struct PairsVisitor<M>(PhantomPtr<M>);
+
+    impl<'de, M: TotalMap> Visitor<'de> for PairsVisitor<M>
+    where
+        M::Key: Deserialize<'de>,
+        M::Value: Deserialize<'de>,
+    {
+        type Value = M;
+
+        fn expecting(&self, f: &mut Formatter) -> fmt::Result {
+            f.write_str("a sequence of key-value pairs")
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut access: A) -> Result<M, A::Error> {
+            let mut map = M::default();
+            while let Some((key, value)) = access.next_element()? {
+                map.insert(key, value);
+            }
+            Ok(map)
+        }
+    }
+}
+
+// --------------------------------------------------------------------------
+// Implementation details
+
+// The operations needed to populate either kind of map.
+trait TotalMap: Default {
+    type Key;
+    type Value;
+    fn insert(&mut self, key: Self::Key, value: Self::Value);
+    fn len(&self) -> usize;
+    fn is_common(value: &Self::Value) -> bool;
+}
+
+impl<K: Eq + Hash, V, C: Commonality<V>, S: BuildHasher + Default> TotalMap
+    for TotalHashMap<K, V, C, S>
+{
+    type Key = K;
+    type Value = V;
+    fn insert(&mut self, key: K, value: V) {
+        TotalHashMap::insert(self, key, value);
+    }
+    fn len(&self) -> usize {
+        TotalHashMap::len(self)
+    }
+    fn is_common(value: &V) -> bool {
+        C::is_common(value)
+    }
+}
+
+impl<K: Ord, V, C: Commonality<V>> TotalMap for TotalBTreeMap<K, V, C> {
+    type Key = K;
+    type Value = V;
+    fn insert(&mut self, key: K, value: V) {
+        TotalBTreeMap::insert(self, key, value);
+    }
+    fn len(&self) -> usize {
+        TotalBTreeMap::len(self)
+    }
+    fn is_common(value: &V) -> bool {
+        C::is_common(value)
+    }
+}
+
+struct MapVisitor<M> {
+    max_len: usize,
+    _map: PhantomPtr<M>,
+}
+
+impl<M> MapVisitor<M> {
+    fn new(max_len: usize) -> Self {
+        Self { max_len, _map: PhantomPtr::default() }
+    }
+}
+
+impl<'de, M: TotalMap> Visitor<'de> for MapVisitor<M>
+where
+    M::Key: Deserialize<'de>,
+    M::Value: Deserialize<'de>,
+{
+    type Value = M;
+
+    fn expecting(&self, f: &mut Formatter) -> fmt::Result {
+        expecting_map(f, self.max_len)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, access: A) -> Result<M, A::Error> {
+        let mut map = M::default();
+        ExtendVisitor { map: &mut map, max_len: self.max_len }.visit_map(access)?;
+        Ok(map)
+    }
+}
+
+// Inserts the entries of a serialized map into an existing map.
+struct ExtendVisitor<'a, M> {
+    map: &'a mut M,
+    max_len: usize,
+}
+
+impl<'de, M: TotalMap> Visitor<'de> for ExtendVisitor<'_, M>
+where
+    M::Key: Deserialize<'de>,
+    M::Value: Deserialize<'de>,
+{
+    type Value = ();
+
+    fn expecting(&self, f: &mut Formatter) -> fmt::Result {
+        expecting_map(f, self.max_len)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<(), A::Error> {
+        while let Some((key, value)) = access.next_entry()? {
+            self.map.insert(key, value);
+            let len = self.map.len();
+            if len > self.max_len {
+                return Err(A::Error::invalid_length(len, &self));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Rejects entries with common values, naming the key if `name_key` can.
+struct StrictVisitor<M: TotalMap> {
+    name_key: fn(&M::Key) -> Option<String>,
+    _map: PhantomPtr<M>,
+}
+
+impl<M: TotalMap> StrictVisitor<M> {
+    fn new(name_key: fn(&M::Key) -> Option<String>) -> Self {
+        Self { name_key, _map: PhantomPtr::default() }
+    }
+}
+
+impl<'de, M: TotalMap> Visitor<'de> for StrictVisitor<M>
+where
+    M::Key: Deserialize<'de>,
+    M::Value: Deserialize<'de>,
+{
+    type Value = M;
+
+    fn expecting(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str("a map with no common values")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<M, A::Error> {
+        let mut map = M::default();
+        while let Some((key, value)) = access.next_entry::<M::Key, M::Value>()? {
+            if M::is_common(&value) {
+                return Err(match (self.name_key)(&key) {
+                    Some(key) => A::Error::custom(format_args!(
+                        "key {key} is associated with the common value"
+                    )),
+                    None => A::Error::custom("a key is associated with the common value"),
+                });
+            }
+            map.insert(key, value);
+        }
+        Ok(map)
+    }
+}
+
+fn expecting_map(f: &mut Formatter, max_len: usize) -> fmt::Result {
+    if max_len == usize::MAX {
+        f.write_str("a map")
+    } else {
+        write!(f, "a map with at most {max_len} uncommon entries")
+    }
+}
+
+fn serialize_with_common<V, M, Ser>(
+    common: &V,
+    map: &M,
+    serializer: Ser,
+) -> Result<Ser::Ok, Ser::Error>
+where
+    V: Serialize,
+    M: Serialize,
+    Ser: Serializer,
+{
+    let mut tuple = serializer.serialize_tuple(2)?;
+    tuple.serialize_element(common)?;
+    tuple.serialize_element(map)?;
+    tuple.end()
+}
+
+struct WithCommonVisitor<M>(PhantomPtr<M>);
+
+impl<'de, M: TotalMap + Deserialize<'de>> Visitor<'de> for WithCommonVisitor<M>
+where
+    M::Value: Deserialize<'de>,
+{
+    type Value = WithCommon<M>;
+
+    fn expecting(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str("a pair of a common value and a map")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
+        let common: M::Value =
+            access.next_element()?.ok_or_else(|| A::Error::invalid_length(0, &self))?;
+        let map: M = access.next_element()?.ok_or_else(|| A::Error::invalid_length(1, &self))?;
+        if !M::is_common(&common) {
+            return Err(A::Error::custom("common value does not match the map's commonality"));
+        }
+        Ok(WithCommon(map))
+    }
+}
@@ -0,0 +1,206 @@
+//! Provides [ShardedTotalHashMap], a [TotalHashMap] split into independently locked shards for
+//! concurrent access.
+
+use std::{
+    borrow::Borrow,
+    collections::hash_map::RandomState,
+    fmt::{self, Debug, Formatter},
+    hash::{BuildHasher, Hash},
+    ops::AddAssign,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+use crate::{Commonality, DefaultCommonality, TotalHashMap};
+
+// --------------------------------------------------------------------------
+
+/// A thread-safe map in which every possible key has an associated value, suited to concurrent
+/// updates such as high-contention sparse counters.
+///
+/// Keys are partitioned by hash across a fixed number of shards, each of which is a [TotalHashMap]
+/// behind its own [Mutex]. Operations on keys in different shards don't contend with each other.
+///
+/// # Consistency
+///
+/// Each method that takes a key locks only that key's shard, so operations on a single key are
+/// atomic. There are no snapshots across shards: methods that visit the whole map, such as
+/// [`len()`](Self::len), lock one shard at a time, and may observe concurrent updates to some
+/// shards but not others.
+///
+/// A panic while a shard is locked doesn't poison the map. The shard's entries remain valid, since
+/// every update leaves each entry either stored with an *uncommon* value or removed.
+pub struct ShardedTotalHashMap<K, V, C = DefaultCommonality, S = RandomState> {
+    shards: Box<[Shard<K, V, C, S>]>,
+    hasher: S,
+}
+
+type Shard<K, V, C, S> = Mutex<TotalHashMap<K, V, C, S>>;
+
+/// The number of shards used by [ShardedTotalHashMap::new].
+const DEFAULT_SHARDS: usize = 16;
+
+impl<K, V, C: Commonality<V>> ShardedTotalHashMap<K, V, C> {
+    /// Constructs a `ShardedTotalHashMap` with a default number of shards, in which all keys are
+    /// associated with the *common* value.
+    pub fn new() -> Self {
+        Self::with_shards(DEFAULT_SHARDS)
+    }
+}
+
+impl<K, V, C: Commonality<V>, S: BuildHasher + Default> ShardedTotalHashMap<K, V, C, S> {
+    /// Constructs a `ShardedTotalHashMap` with the given number of shards, in which all keys are
+    /// associated with the *common* value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `shards` is zero.
+    pub fn with_shards(shards: usize) -> Self {
+        assert!(shards > 0, "a ShardedTotalHashMap needs at least one shard");
+        Self {
+            shards: (0..shards).map(|_| Mutex::new(TotalHashMap::default())).collect(),
+            hasher: S::default(),
+        }
+    }
+}
+
+impl<K, V, C: Commonality<V>, S: BuildHasher + Default> Default
+    for ShardedTotalHashMap<K, V, C, S>
+{
+    fn default() -> Self {
+        Self::with_shards(DEFAULT_SHARDS)
+    }
+}
+
+impl<K, V, C, S> ShardedTotalHashMap<K, V, C, S> {
+    /// Returns the number of shards in the map.
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+    /// Returns the number of *uncommon* entries in the map. See the
+    /// [consistency model](Self#consistency) for how this behaves under concurrent updates.
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| lock(shard).len()).sum()
+    }
+    /// Returns true if the map contains no *uncommon* entries. See the
+    /// [consistency model](Self#consistency) for how this behaves under concurrent updates.
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(|shard| lock(shard).is_empty())
+    }
+    /// Merges the shards into a single [TotalHashMap].
+    pub fn into_inner(self) -> TotalHashMap<K, V, C, S>
+    where
+        K: Eq + Hash,
+        C: Commonality<V>,
+        S: BuildHasher + Default,
+    {
+        let mut map = TotalHashMap::default();
+        for shard in self.shards.into_vec() {
+            let shard = shard.into_inner().unwrap_or_else(PoisonError::into_inner);
+            map.extend(shard);
+        }
+        map
+    }
+}
+
+// --------------------------------------------------------------------------
+// Element access
+
+impl<K: Eq + Hash, V, C, S: BuildHasher> ShardedTotalHashMap<K, V, C, S> {
+    /// Returns a copy of the value associated with the given key.
+    pub fn get<Q>(&self, key: &Q) -> V
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+        V: Clone,
+    {
+        self.shard(key).get(key).clone()
+    }
+    /// Returns true if the map contains an *uncommon* entry with the given key.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.shard(key).contains_key(key)
+    }
+
+    fn shard<Q>(&self, key: &Q) -> MutexGuard<'_, TotalHashMap<K, V, C, S>>
+    where
+        Q: Hash + ?Sized,
+    {
+        let index = self.hasher.hash_one(key) as usize % self.shards.len();
+        lock(&self.shards[index])
+    }
+}
+
+// --------------------------------------------------------------------------
+// Mutation
+
+impl<K: Eq + Hash, V, C: Commonality<V>, S: BuildHasher> ShardedTotalHashMap<K, V, C, S> {
+    /// Associates a key with a value in the map, and returns the value previously associated with
+    /// that key.
+    pub fn insert(&self, key: K, value: V) -> V {
+        self.shard(&key).insert(key, value)
+    }
+    /// Associates a key with the *common* value in the map, and returns the value previously
+    /// associated with that key.
+    pub fn remove<Q>(&self, key: &Q) -> V
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.shard(key).remove(key)
+    }
+    /// Calls `f` with a mutable reference to the value associated with the given key, while holding
+    /// the lock on the key's shard, and returns its result. If the value is *common* when `f`
+    /// returns, the key is reset to the *common* value.
+    ///
+    /// Other keys in the same shard can't be accessed until `f` returns; calling back into the map
+    /// from `f` may deadlock.
+    pub fn update<R>(&self, key: K, f: impl FnOnce(&mut V) -> R) -> R {
+        let mut shard = self.shard(&key);
+        let mut entry = shard.entry(key);
+        f(&mut entry)
+    }
+}
+
+impl<K, V, C, S> ShardedTotalHashMap<K, V, C, S>
+where
+    K: Eq + Hash,
+    V: AddAssign,
+    C: Commonality<V>,
+    S: BuildHasher,
+{
+    /// Atomically adds `delta` to the value associated with the given key. If the sum is *common*,
+    /// the key is reset to the *common* value.
+    pub fn add(&self, key: K, delta: V) {
+        self.update(key, |value| *value += delta)
+    }
+}
+
+// --------------------------------------------------------------------------
+// Miscellaneous traits
+
+impl<K, V, C, S> From<ShardedTotalHashMap<K, V, C, S>> for TotalHashMap<K, V, C, S>
+where
+    K: Eq + Hash,
+    C: Commonality<V>,
+    S: BuildHasher + Default,
+{
+    fn from(map: ShardedTotalHashMap<K, V, C, S>) -> Self {
+        map.into_inner()
+    }
+}
+
+impl<K, V, C, S> Debug for ShardedTotalHashMap<K, V, C, S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShardedTotalHashMap").field("shards", &self.shards.len()).finish()
+    }
+}
+
+// --------------------------------------------------------------------------
+// Implementation details
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
@@ -0,0 +1,208 @@
+//! Provides [StrictTotalHashMap], a wrapper around [TotalHashMap] that treats inserting the
+//! *common* value as a logic error.
+
+use std::{
+    borrow::Borrow,
+    collections::hash_map::RandomState,
+    error::Error,
+    fmt::{self, Debug, Display, Formatter},
+    hash::{BuildHasher, Hash},
+    ops::{Deref, DerefMut},
+    thread,
+};
+
+use crate::{hash_map::Entry, Commonality, DefaultCommonality, TotalHashMap};
+
+// --------------------------------------------------------------------------
+
+/// A [TotalHashMap] that panics, rather than silently resetting the entry, when a key is associated
+/// with the *common* value through [`insert()`](Self::insert) or [`entry()`](Self::entry).
+///
+/// This catches bugs where code writes the *common* value expecting it to be stored. Keys can
+/// still be reset to the *common* value explicitly, by calling [`remove()`](Self::remove).
+///
+/// The wrapper dereferences to the underlying [TotalHashMap] for read-only access, and has no
+/// runtime cost beyond the checks in the methods above. It doesn't implement serde's traits; to
+/// deserialize a strict map, deserialize a [TotalHashMap] and convert it with [From].
+pub struct StrictTotalHashMap<K, V, C = DefaultCommonality, S = RandomState>(
+    TotalHashMap<K, V, C, S>,
+);
+
+impl<K: Clone, V: Clone, C, S: Clone> Clone for StrictTotalHashMap<K, V, C, S> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<K, V, C: Commonality<V>, S: Default> Default for StrictTotalHashMap<K, V, C, S> {
+    fn default() -> Self {
+        Self(TotalHashMap::default())
+    }
+}
+impl<K, V, C: Commonality<V>> StrictTotalHashMap<K, V, C> {
+    /// Constructs a `StrictTotalHashMap` in which all keys are associated with the *common* value.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+impl<K, V, C: Commonality<V>> TotalHashMap<K, V, C> {
+    /// Constructs a [StrictTotalHashMap] in which all keys are associated with the *common* value.
+    pub fn strict() -> StrictTotalHashMap<K, V, C> {
+        StrictTotalHashMap::new()
+    }
+}
+
+impl<K, V, C, S> StrictTotalHashMap<K, V, C, S> {
+    /// Unwraps the underlying [TotalHashMap].
+    pub fn into_inner(self) -> TotalHashMap<K, V, C, S> {
+        self.0
+    }
+}
+
+impl<K, V, C, S> From<TotalHashMap<K, V, C, S>> for StrictTotalHashMap<K, V, C, S> {
+    fn from(map: TotalHashMap<K, V, C, S>) -> Self {
+        Self(map)
+    }
+}
+impl<K, V, C, S> From<StrictTotalHashMap<K, V, C, S>> for TotalHashMap<K, V, C, S> {
+    fn from(map: StrictTotalHashMap<K, V, C, S>) -> Self {
+        map.0
+    }
+}
+
+impl<K, V, C, S> Deref for StrictTotalHashMap<K, V, C, S> {
+    type Target = TotalHashMap<K, V, C, S>;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+// --------------------------------------------------------------------------
+// Population
+
+impl<K: Eq + Hash, V, C: Commonality<V>, S: BuildHasher> StrictTotalHashMap<K, V, C, S> {
+    /// Associates a key with an *uncommon* value in the map, and returns the value previously
+    /// associated with that key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `value` is *common*.
+    pub fn insert(&mut self, key: K, value: V) -> V {
+        if C::is_common(&value) {
+            panic!("inserted the common value into a StrictTotalHashMap");
+        }
+        self.0.insert(key, value)
+    }
+
+    /// Associates a key with a value in the map, and returns the value previously associated with
+    /// that key. Returns an error, leaving the map unchanged, if `value` is *common*.
+    pub fn try_insert_strict(&mut self, key: K, value: V) -> Result<V, CommonValueError<K, V>> {
+        if C::is_common(&value) {
+            return Err(CommonValueError { key, value });
+        }
+        Ok(self.0.insert(key, value))
+    }
+
+    /// Associates a key with the *common* value in the map, and returns the value previously
+    /// associated with that key. This is the only way to reset a key to the *common* value.
+    pub fn remove<Q>(&mut self, key: &Q) -> V
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.0.remove(key)
+    }
+
+    /// Gets the given key's associated entry in the map for in-place manipulation. See
+    /// [StrictEntry] for details.
+    pub fn entry(&mut self, key: K) -> StrictEntry<'_, K, V, C> {
+        StrictEntry { entry: self.0.entry(key), written: false }
+    }
+}
+
+/// A view into a single entry in a [StrictTotalHashMap].
+///
+/// When the view is dropped, it panics if the entry's value was written through the view (with
+/// [DerefMut]) and is now *common*. Reading the value of a vacant entry, or leaving an entry
+/// untouched, never panics. The entry is still reset to the *common* value while unwinding, so the
+/// map remains valid if the panic is caught. (If the view is dropped while the thread is already
+/// panicking, it doesn't panic again.)
+///
+/// This view is constructed from [StrictTotalHashMap::entry].
+pub struct StrictEntry<'a, K, V, C: Commonality<V> = DefaultCommonality> {
+    entry: Entry<'a, K, V, C>,
+    written: bool,
+}
+
+impl<K, V, C: Commonality<V>> Deref for StrictEntry<'_, K, V, C> {
+    type Target = V;
+    fn deref(&self) -> &Self::Target {
+        &self.entry
+    }
+}
+impl<K, V, C: Commonality<V>> DerefMut for StrictEntry<'_, K, V, C> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.written = true;
+        &mut self.entry
+    }
+}
+
+impl<K, V, C: Commonality<V>> Drop for StrictEntry<'_, K, V, C> {
+    fn drop(&mut self) {
+        if self.written && C::is_common(&self.entry) && !thread::panicking() {
+            panic!("set an entry of a StrictTotalHashMap to the common value");
+        }
+    }
+}
+
+impl<K: Debug, V: Debug, C: Commonality<V>> Debug for StrictEntry<'_, K, V, C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("StrictEntry").field(&self.entry).finish()
+    }
+}
+
+/// The error returned by [StrictTotalHashMap::try_insert_strict] when the inserted value is
+/// *common*. It contains the key and value that were not inserted.
+pub struct CommonValueError<K, V> {
+    /// The key that was not inserted.
+    pub key: K,
+    /// The *common* value that was not inserted.
+    pub value: V,
+}
+
+impl<K: Debug, V: Debug> Debug for CommonValueError<K, V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CommonValueError")
+            .field("key", &self.key)
+            .field("value", &self.value)
+            .finish()
+    }
+}
+impl<K, V> Display for CommonValueError<K, V> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "inserted the common value into a StrictTotalHashMap")
+    }
+}
+impl<K: Debug, V: Debug> Error for CommonValueError<K, V> {}
+
+// --------------------------------------------------------------------------
+// Miscellaneous traits
+
+impl<K, V, C, S1, S2> PartialEq<StrictTotalHashMap<K, V, C, S2>> for StrictTotalHashMap<K, V, C, S1>
+where
+    K: Eq + Hash,
+    V: PartialEq,
+    S1: BuildHasher,
+    S2: BuildHasher,
+{
+    fn eq(&self, other: &StrictTotalHashMap<K, V, C, S2>) -> bool {
+        self.0 == other.0
+    }
+}
+impl<K: Eq + Hash, V: Eq, C, S: BuildHasher> Eq for StrictTotalHashMap<K, V, C, S> {}
+
+impl<K: Debug, V: Debug, C, S> Debug for StrictTotalHashMap<K, V, C, S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("StrictTotalHashMap").field(&self.0).finish()
+    }
+}
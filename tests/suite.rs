@@ -1,5 +1,34 @@
+use std::{
+    cell::Cell,
+    cmp::Ordering,
+    collections::{
+        hash_map::{DefaultHasher, RandomState},
+        BTreeMap, HashMap, HashSet,
+    },
+    hash::{BuildHasher, BuildHasherDefault},
+    iter::FusedIterator,
+    mem,
+    ops::Bound,
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Mutex},
+    thread,
+};
+
 use itertools::Itertools;
-use total_maps::{Commonality, TotalBTreeMap, TotalHashMap};
+#[cfg(feature = "serde")]
+use serde::de::DeserializeSeed;
+use total_maps::{
+    float::Threshold, BoundedTotalHashMap, CommonValuePresent, Commonality, Comparator,
+    DefaultCommonality, EmptyCommonality, FalseCommonality, FrozenTotalMap, NaNCommonality,
+    NoneCommonality, Occupancy, OrderBy, ShardedTotalHashMap, SparseBTreeMap, SparseHashMap,
+    ThresholdCommonality, TotalBTreeMap, TotalBTreeMapBy, TotalHashMap, TotalHashMapDyn,
+    TrueCommonality,
+};
+#[cfg(feature = "num-traits")]
+use total_maps::{
+    AboveMinBTreeMap, BelowMaxHashMap, MaxCommonality, MinCommonality, PairCommonality,
+    ZeroCommonality,
+};
 
 macro_rules! common {
     (mod $mod:ident, $Map:ident, $as_inner_mut:ident, $iter_eq:expr $(,)?) => {
@@ -54,6 +83,23 @@ macro_rules! common {
                 assert!(m.into_iter().next().is_none());
             }
 
+            #[test]
+            fn insert_remove_opt() {
+                let mut m = $Map::<_, _>::new();
+                assert_eq!(m.insert_opt("foo", "bar"), None);
+                assert_eq!(m.insert_opt("foo", "baz"), Some("bar"));
+                assert_eq!(m.insert_opt("quux", ""), None);
+                assert_eq!(m.len(), 1);
+
+                assert_eq!(m.remove_opt(&"quux"), None);
+                assert_eq!(m.remove_opt(&"foo"), Some("baz"));
+                assert!(m.is_empty());
+
+                m.insert("foo", "bar");
+                assert_eq!(m.insert_opt("foo", ""), Some("bar"));
+                assert!(m.is_empty());
+            }
+
             #[test]
             fn access() {
                 let mut m = $Map::<_, _>::new();
@@ -73,6 +119,33 @@ macro_rules! common {
                 assert_eq!(m[&"quux"], "");
             }
 
+            #[test]
+            fn raw_occupancy() {
+                let mut m = $Map::<_, _>::new();
+                m.insert("foo", "bar");
+                m.insert("baz", "");
+
+                assert_eq!(m.raw_occupancy(&"foo"), Occupancy::UncommonStored);
+                assert_eq!(m.raw_occupancy(&"baz"), Occupancy::CommonImplicit);
+                assert_eq!(m.raw_occupancy(&"quux"), Occupancy::CommonImplicit);
+
+                m.remove(&"foo");
+                assert_eq!(m.raw_occupancy(&"foo"), Occupancy::CommonImplicit);
+            }
+
+            #[test]
+            fn get_all() {
+                let mut m = $Map::<_, _>::new();
+                assert_eq!(m.insert("foo", "bar"), "");
+                assert_eq!(m.insert("baz", "quux"), "");
+
+                assert_eq!(
+                    m.get_all(&["baz", "xyzzy", "foo", "baz"]),
+                    [&"quux", &"", &"bar", &"quux"]
+                );
+                assert!(m.get_all::<str>(&[]).is_empty());
+            }
+
             #[test]
             fn entry_mut() {
                 let mut m = $Map::<_, _>::new();
@@ -101,6 +174,333 @@ macro_rules! common {
                 assert!(!m.contains_key(&"foo"));
             }
 
+            #[test]
+            fn add() {
+                let mut m = $Map::<_, i32>::new();
+                m.add("foo", 3);
+                m.add("bar", 0);
+                assert_eq!(m.get(&"foo"), &3);
+                assert!(!m.contains_key(&"bar"));
+
+                m.add("foo", 2);
+                assert_eq!(m.get(&"foo"), &5);
+                m.add("foo", -5);
+                assert!(!m.contains_key(&"foo"));
+                assert!(m.is_empty());
+            }
+
+            #[cfg(feature = "num-traits")]
+            #[test]
+            fn increment() {
+                let mut m = $Map::<_, i32, ZeroCommonality>::new();
+                m.increment("foo");
+                m.increment("foo");
+                m.increment("bar");
+                assert_eq!(m.get(&"foo"), &2);
+                assert_eq!(m.get(&"bar"), &1);
+
+                m.add("bar", -1);
+                assert!(!m.contains_key(&"bar"));
+                assert_eq!(m.len(), 1);
+            }
+
+            #[cfg(feature = "num-traits")]
+            #[test]
+            fn decrement() {
+                let mut m = $Map::<_, u32, ZeroCommonality>::new();
+                m.add("foo", 2);
+                assert_eq!(m.decrement("foo"), Some(true));
+                assert_eq!(m.get(&"foo"), &1);
+                assert_eq!(m.decrement("foo"), Some(false));
+                assert!(!m.contains_key(&"foo"));
+
+                assert_eq!(m.decrement("foo"), None);
+                assert!(!m.contains_key(&"foo"));
+
+                m.add("bar", 5);
+                assert_eq!(m.sub("bar", 6), None);
+                assert_eq!(m.get(&"bar"), &5);
+                assert_eq!(m.sub("bar", 3), Some(true));
+                assert_eq!(m.sub("bar", 2), Some(false));
+                assert!(m.is_empty());
+            }
+
+            #[test]
+            fn get_cow() {
+                let mut m = $Map::<_, String>::new();
+                m.insert("foo", "bar".into());
+                assert_eq!(*m.get_cow(&"foo"), "bar");
+                assert_eq!(*m.get_cow(&"baz"), "");
+                assert_eq!(m.get_cow(&"baz").into_owned(), String::new());
+            }
+
+            #[test]
+            fn value_counts() {
+                let m = [("a", 1), ("b", 2), ("c", 1), ("d", 0), ("e", 2), ("f", 3)]
+                    .into_iter()
+                    .collect::<$Map<_, _>>();
+                let counts = m.value_counts();
+                assert_eq!(counts.len(), 3);
+                assert_eq!(counts[&1], 2);
+                assert_eq!(counts[&2], 2);
+                assert_eq!(counts[&3], 1);
+                assert!(!counts.contains_key(&0));
+                assert!($Map::<&str, i32>::new().value_counts().is_empty());
+            }
+
+            #[test]
+            fn into_sorted_by_value() {
+                let m = [("a", 3), ("b", 0), ("c", 1), ("d", 2)];
+                let m = m.into_iter().collect::<$Map<_, _>>();
+                assert_eq!(m.clone().into_sorted_by_value(), [("c", 1), ("d", 2), ("a", 3)]);
+                assert_eq!(m.clone().into_sorted_by_value_desc(), [("a", 3), ("d", 2), ("c", 1)]);
+                let by_parity = |lhs: &i32, rhs: &i32| (lhs % 2).cmp(&(rhs % 2)).then(lhs.cmp(rhs));
+                assert_eq!(m.into_sorted_by_value_by(by_parity), [("d", 2), ("c", 1), ("a", 3)]);
+            }
+
+            #[test]
+            fn summary() {
+                let mut m = $Map::<_, i32>::new();
+                assert!(m.is_trivial());
+                assert_eq!(m.summary(), "0 uncommon entries, common = 0");
+                m.insert("foo", 1);
+                assert_eq!(m.summary(), "1 uncommon entry, common = 0");
+                m.insert("bar", 2);
+                m.insert("baz", 0);
+                assert_eq!(m.effective_len(), 2);
+                assert!(!m.is_trivial());
+                assert_eq!(m.summary(), "2 uncommon entries, common = 0");
+            }
+
+            #[test]
+            fn len_after_insert() {
+                let mut m = $Map::<_, _>::new();
+                m.insert("foo", 1);
+                assert!(m.would_store(&1));
+                assert!(!m.would_store(&0));
+                assert_eq!(m.len_after_insert(&"foo", &2), 1);
+                assert_eq!(m.len_after_insert(&"foo", &0), 0);
+                assert_eq!(m.len_after_insert(&"bar", &2), 2);
+                assert_eq!(m.len_after_insert(&"bar", &0), 1);
+                assert_eq!(m.len(), 1);
+            }
+
+            #[test]
+            fn remove_entry() {
+                let mut m = $Map::<_, _>::new();
+                m.insert(String::from("foo"), 1);
+                assert_eq!(m.remove_entry("foo"), Some((String::from("foo"), 1)));
+                assert_eq!(m.remove_entry("foo"), None);
+                assert!(m.is_empty());
+            }
+
+            #[test]
+            fn entry_panic() {
+                let mut m = $Map::<_, _>::new();
+                m.insert("foo", 1);
+                let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                    let mut entry = m.entry("foo");
+                    *entry = 0;
+                    panic!("while holding an entry");
+                }));
+                assert!(result.is_err());
+                let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                    let mut entry = m.entry("bar");
+                    *entry = 2;
+                    panic!("while holding an entry");
+                }));
+                assert!(result.is_err());
+                assert_iter_eq(m.iter(), [(&"bar", &2)], $iter_eq);
+                assert!(m.is_canonical());
+            }
+
+            #[test]
+            fn entry_ref() {
+                let mut m = $Map::<String, i32>::new();
+                *m.entry_ref("foo") += 2;
+                assert_eq!(m.get("foo"), &2);
+                *m.entry_ref("foo") -= 2;
+                assert!(m.is_empty());
+
+                let entry = m.entry_ref("bar");
+                assert_eq!(*entry, 0);
+                drop(entry);
+                assert!(m.is_empty());
+            }
+
+            #[test]
+            fn entry_ref_or_insert() {
+                let mut m = $Map::<String, i32>::new();
+                *m.entry_ref_or_insert("foo", 1) += 1;
+                assert_eq!(m.get("foo"), &2);
+                *m.entry_ref_or_insert("foo", 10) += 1;
+                assert_eq!(m.get("foo"), &3);
+                *m.entry_ref_or_insert("foo", 10) = 0;
+                assert!(!m.contains_key("foo"));
+
+                let entry = m.entry_ref_or_insert("bar", 0);
+                assert_eq!(*entry, 0);
+                assert_eq!(format!("{entry:?}"), r#"EntryRef("bar", 0)"#);
+                drop(entry);
+                assert!(m.is_empty());
+            }
+
+            #[test]
+            fn describe() {
+                let mut m = $Map::<_, _>::new();
+                m.insert("foo", "bar");
+                let (common, entries) = m.describe();
+                assert_eq!(common, &"");
+                assert_iter_eq(entries, [(&"foo", &"bar")], $iter_eq);
+            }
+
+            #[test]
+            fn reset_where() {
+                let mut m = (1..=6).map(|i| (i, i * 10)).collect::<$Map<_, _>>();
+                m.reset_where(|key, value| key % 2 == 0 || *value > 40);
+                assert_iter_eq(m.iter(), [(&1, &10), (&3, &30)], $iter_eq);
+                assert_eq!(m.get(&2), &0);
+            }
+
+            #[test]
+            fn from_parts() {
+                let m = $Map::<_, _>::from_parts([("foo", 1)].into_iter().collect(), 0);
+                assert_iter_eq(m.iter(), [(&"foo", &1)], $iter_eq);
+                assert_eq!(m.get(&"bar"), &0);
+            }
+
+            #[cfg(debug_assertions)]
+            #[test]
+            #[should_panic]
+            fn from_parts_common() {
+                $Map::<_, _>::from_parts([("foo", 0)].into_iter().collect(), 0);
+            }
+
+            #[test]
+            fn value_delta() {
+                let m1 = [("a", 1), ("b", 1), ("c", 2), ("d", 3)];
+                let m1 = m1.into_iter().collect::<$Map<_, _>>();
+                let m2 = [("a", 1), ("b", 2), ("c", 2), ("e", 4)];
+                let m2 = m2.into_iter().collect::<$Map<_, _>>();
+                let delta = m1.value_delta(&m2);
+                assert_eq!(delta.len(), 4);
+                assert_eq!(delta[&1], 1);
+                assert_eq!(delta[&2], -1);
+                assert_eq!(delta[&3], 1);
+                assert_eq!(delta[&4], -1);
+                assert!(m1.value_delta(&m1).is_empty());
+            }
+
+            #[test]
+            fn entry_or_return() {
+                let mut m = $Map::<String, i32>::new();
+                assert_eq!(m.entry_or_return("foo".to_owned()).unwrap_err(), "foo");
+                assert!(m.is_empty());
+                m.insert("foo".to_owned(), 1);
+                *m.entry_or_return("foo".to_owned()).unwrap() -= 1;
+                assert!(m.is_empty());
+            }
+
+            #[test]
+            fn common_keys() {
+                let mut m = $Map::<Ordering, i32>::new();
+                assert!(m.common_keys().eq([Ordering::Less, Ordering::Equal, Ordering::Greater]));
+                m.insert(Ordering::Equal, 1);
+                assert!(m.common_keys().eq([Ordering::Less, Ordering::Greater]));
+                m.insert(Ordering::Less, 2);
+                m.insert(Ordering::Greater, 3);
+                assert_eq!(m.common_keys().count(), 0);
+            }
+
+            #[test]
+            fn compose() {
+                let mut f = $Map::<_, i32>::new();
+                f.insert("a", 1);
+                f.insert("b", 2);
+                f.insert("c", 3);
+                let mut g = $Map::<_, &str>::new();
+                g.insert(1, "one");
+                g.insert(3, "three");
+                g.insert(4, "four");
+
+                let h: $Map<_, _> = f.compose(&g).unwrap();
+                $iter_eq(h.iter(), [(&"a", &"one"), (&"c", &"three")]);
+                assert_eq!(h.get("z"), &"");
+
+                g.insert(0, "zero");
+                let h: Option<$Map<_, _>> = f.compose(&g);
+                assert!(h.is_none());
+            }
+
+            #[test]
+            fn get_key_value() {
+                let mut m = $Map::<String, i32>::new();
+                m.insert("foo".into(), 1);
+                m.insert("bar".into(), 0);
+                assert_eq!(m.get_key_value("foo"), Some((&"foo".to_owned(), &1)));
+                assert_eq!(m.get_key_value("bar"), None);
+                assert_eq!(m.get_key_value("baz"), None);
+            }
+
+            #[test]
+            fn try_insert() {
+                let mut m = $Map::<_, i32>::new();
+                assert_eq!(m.try_insert("foo", 1).unwrap(), &1);
+                assert_eq!(m.try_insert("bar", 0).unwrap(), &0);
+                assert!(!m.contains_key("bar"));
+
+                let mut err = m.try_insert("foo", 2).unwrap_err();
+                assert_eq!(err.value, 2);
+                assert_eq!(*err.entry, 1);
+                *err.entry = 3;
+                drop(err);
+                assert_eq!(m.get("foo"), &3);
+            }
+
+            #[test]
+            fn append() {
+                let mut m = $Map::<_, i32>::new();
+                m.insert("a", 1);
+                m.insert("b", 2);
+                let mut other = $Map::<_, i32>::new();
+                other.insert("b", 20);
+                other.insert("c", 30);
+                m.append(&mut other);
+                $iter_eq(m.iter(), [(&"a", &1), (&"b", &20), (&"c", &30)]);
+                assert!(other.is_empty());
+                assert_eq!(other.get("c"), &0);
+            }
+
+            #[test]
+            fn retain_keys() {
+                let mut m = $Map::<_, i32>::new();
+                m.insert("foo", 1);
+                m.insert("bar", 2);
+                m.insert("baz", 3);
+
+                let mut visited = vec![];
+                m.retain_keys(["foo", "bar", "quux", "xyzzy"], |key, value| {
+                    visited.push(*key);
+                    match *key {
+                        "foo" => *value == 1,
+                        "bar" => {
+                            *value = 0;
+                            true
+                        }
+                        "quux" => {
+                            *value = 4;
+                            true
+                        }
+                        _ => false,
+                    }
+                });
+                assert_eq!(visited, ["foo", "bar", "quux", "xyzzy"]);
+                assert_iter_eq(m.iter(), [(&"baz", &3), (&"foo", &1), (&"quux", &4)], $iter_eq);
+
+                m.retain_keys(["foo", "baz"], |_, _| false);
+                assert_iter_eq(m.iter(), [(&"quux", &4)], $iter_eq);
+            }
+
             #[test]
             fn as_inner_mut() {
                 let mut m = $Map::<_, _>::new();
@@ -125,6 +525,149 @@ macro_rules! common {
                 assert_iter_eq(m.iter(), [(&"foo", &"bar2")], $iter_eq);
             }
 
+            #[test]
+            fn values_mut() {
+                let mut m = $Map::<_, _>::new();
+                assert_eq!(m.insert("foo", "bar"), "");
+                assert_eq!(m.insert("baz", "quux"), "");
+
+                let mut values = m.values_mut();
+                assert_eq!(values.len(), 2);
+                while let Some(value) = values.next() {
+                    *value = if *value == "bar" { "bar2" } else { "" };
+                }
+                assert!(values.is_empty());
+                drop(values);
+
+                assert_eq!(m.len(), 1);
+                assert!(!m.contains_key("baz"));
+                assert!(m.is_canonical());
+                assert_iter_eq(m.iter(), [(&"foo", &"bar2")], $iter_eq);
+            }
+
+            #[test]
+            fn iter_mut() {
+                let mut m = $Map::<_, _>::new();
+                assert_eq!(m.insert("foo", 1), 0);
+                assert_eq!(m.insert("bar", 2), 0);
+                assert_eq!(m.insert("baz", 3), 0);
+
+                let mut it = m.iter_mut();
+                let mut keys = vec![];
+                while let Some((key, value)) = it.next() {
+                    keys.push(*key);
+                    *value = if *key == "bar" { 0 } else { *value * 10 };
+                }
+                drop(it);
+                assert_iter_eq(keys.into_iter(), ["bar", "baz", "foo"], $iter_eq);
+
+                assert!(m.is_canonical());
+                assert_iter_eq(m.iter(), [(&"baz", &30), (&"foo", &10)], $iter_eq);
+
+                let mut it = m.iter_mut();
+                *it.next().unwrap().1 = 0;
+                std::mem::forget(it);
+                assert_eq!(m.len(), 2);
+                assert!(!m.is_canonical());
+                m.repair();
+                assert_eq!(m.len(), 1);
+            }
+
+            #[test]
+            fn try_common() {
+                struct CheapZero;
+                impl Commonality<i32> for CheapZero {
+                    fn common() -> i32 {
+                        0
+                    }
+                    fn is_common(value: &i32) -> bool {
+                        *value == 0
+                    }
+                    fn try_common() -> Option<i32> {
+                        Some(0)
+                    }
+                }
+                let mut m = $Map::<_, i32, CheapZero>::new();
+                assert_eq!(m.insert_opt("foo", 1), Some(0));
+                assert_eq!(m.insert_opt("foo", 2), Some(1));
+                assert_eq!(m.remove_opt(&"foo"), Some(2));
+                assert_eq!(m.remove_opt(&"foo"), Some(0));
+
+                struct ExpensiveZero;
+                impl Commonality<i32> for ExpensiveZero {
+                    fn common() -> i32 {
+                        0
+                    }
+                    fn is_common(value: &i32) -> bool {
+                        *value == 0
+                    }
+                    fn try_common() -> Option<i32> {
+                        None
+                    }
+                }
+                let mut m = $Map::<_, i32, ExpensiveZero>::new();
+                assert_eq!(m.insert_opt("foo", 1), None);
+                assert_eq!(m.insert_opt("foo", 0), Some(1));
+                assert_eq!(m.remove_opt(&"foo"), None);
+                assert!(m.is_empty());
+            }
+
+            #[test]
+            fn cheap_common() {
+                let calls = CountingCommonality::<false>::calls;
+
+                let mut m = $Map::<_, i32, CountingCommonality<false>>::new();
+                let before = calls();
+                assert_eq!(*m.entry("foo"), 0);
+                assert_eq!(m.remove_opt(&"foo"), None);
+                assert_eq!(m.insert_opt("foo", 0), None);
+                assert_eq!(calls(), before);
+                *m.entry("foo") += 1;
+                assert_eq!(calls(), before + 1);
+                assert_eq!(m[&"foo"], 1);
+
+                let mut m = $Map::<_, i32, CountingCommonality<true>>::new();
+                let before = calls();
+                assert_eq!(*m.entry("foo"), 0);
+                assert_eq!(m.remove_opt(&"foo"), Some(0));
+                assert_eq!(calls(), before + 2);
+                *m.entry("foo") += 1;
+                assert_eq!(m[&"foo"], 1);
+            }
+
+            #[test]
+            fn entry_ref_lazy_common() {
+                let calls = CountingCommonality::<false>::calls;
+                let mut m = $Map::<String, i32, CountingCommonality<false>>::new();
+                m.insert("foo".to_owned(), 1);
+                let before = calls();
+                *m.entry_ref("foo") += 1;
+                assert_eq!(calls(), before);
+                *m.entry_ref("bar") += 1;
+                assert_eq!(calls(), before + 1);
+                assert_eq!(m[&"foo".to_owned()], 2);
+                assert_eq!(m[&"bar".to_owned()], 1);
+            }
+
+            #[test]
+            fn repair() {
+                let mut m = $Map::<_, _>::new();
+                assert_eq!(m.insert("foo", "bar"), "");
+                assert!(m.is_canonical());
+                m.debug_assert_invariant();
+
+                let mut view = m.$as_inner_mut();
+                view.insert("baz", "");
+                std::mem::forget(view);
+                assert!(!m.is_canonical());
+                assert_eq!(m.len(), 2);
+
+                m.repair();
+                assert!(m.is_canonical());
+                m.debug_assert_invariant();
+                assert_iter_eq(m.iter(), [(&"foo", &"bar")], $iter_eq);
+            }
+
             #[test]
             fn from_iter() {
                 let elems =
@@ -142,6 +685,207 @@ macro_rules! common {
                 assert_iter_eq(m.iter(), [(&"foo", &"bar2"), (&"monkey", &"banana")], $iter_eq);
             }
 
+            #[test]
+            fn from_iter_refs() {
+                let mut m = $Map::<_, _>::new();
+                m.insert("foo", "bar");
+                m.insert("baz", "quux");
+
+                let m2 = m.iter().filter(|(key, _)| **key != "baz").collect::<$Map<_, _>>();
+                assert_iter_eq(m2.iter(), [(&"foo", &"bar")], $iter_eq);
+
+                let mut m3 = $Map::<_, _>::new();
+                m3.insert("xyzzy", "plugh");
+                m3.extend(m.iter());
+                m3.extend([(&"xyzzy", &"")]);
+                assert_eq!(m3, m);
+            }
+
+            #[cfg(feature = "serde")]
+            #[test]
+            fn serde_round_trip() {
+                let mut m = $Map::<String, i32>::new();
+                m.insert("foo".into(), 1);
+                m.insert("bar".into(), 2);
+                let json = serde_json::to_string(&m).unwrap();
+                assert_eq!(serde_json::from_str::<$Map<String, i32>>(&json).unwrap(), m);
+
+                let m2 = serde_json::from_str::<$Map<String, i32>>(r#"{"foo":1,"baz":0}"#).unwrap();
+                assert_iter_eq(m2.keys(), [&"foo".to_owned()], $iter_eq);
+            }
+
+            #[cfg(feature = "serde")]
+            #[test]
+            fn serde_bounded() {
+                let json = r#"{"foo":1,"bar":0,"baz":2}"#;
+                let m = total_maps::serde::bounded::<$Map<String, i32>>(2)
+                    .deserialize(&mut serde_json::Deserializer::from_str(json))
+                    .unwrap();
+                assert_eq!(m.len(), 2);
+
+                let err = total_maps::serde::bounded::<$Map<String, i32>>(1)
+                    .deserialize(&mut serde_json::Deserializer::from_str(json))
+                    .unwrap_err();
+                assert!(err.to_string().contains("at most 1 uncommon entries"));
+            }
+
+            #[cfg(feature = "serde")]
+            #[test]
+            fn serde_deserialize_strict() {
+                use std::path::PathBuf;
+                use total_maps::serde::{deserialize_strict, deserialize_strict_named};
+
+                let json = r#"{"foo":1,"bar":2}"#;
+                let m: $Map<String, i32> =
+                    deserialize_strict(&mut serde_json::Deserializer::from_str(json)).unwrap();
+                assert_eq!(m.len(), 2);
+
+                let json = r#"{"foo":1,"bar":0}"#;
+                let err = deserialize_strict_named::<$Map<String, i32>, _>(
+                    &mut serde_json::Deserializer::from_str(json),
+                )
+                .unwrap_err();
+                assert!(err.to_string().contains("key bar is associated with the common value"));
+
+                // PathBuf doesn't implement Display, so the key can't be named.
+                let err = deserialize_strict::<$Map<PathBuf, i32>, _>(
+                    &mut serde_json::Deserializer::from_str(json),
+                )
+                .unwrap_err();
+                assert!(err.to_string().contains("a key is associated with the common value"));
+            }
+
+            #[cfg(feature = "serde")]
+            #[test]
+            fn serde_as_pairs() {
+                use total_maps::serde::as_pairs::{self, Pairs};
+
+                let mut m = $Map::<i32, i32>::new();
+                m.insert(7, 5);
+                let json = serde_json::to_string(&Pairs(&m)).unwrap();
+                assert_eq!(json, "[[7,5]]");
+
+                m.insert(-3, 2);
+                let json = serde_json::to_string(&Pairs(&m)).unwrap();
+                let m2: $Map<i32, i32> =
+                    as_pairs::deserialize(&mut serde_json::Deserializer::from_str(&json)).unwrap();
+                assert_eq!(m2, m);
+
+                let json = "[[1,5],[2,0],[3,6]]";
+                let m: $Map<i32, i32> =
+                    as_pairs::deserialize(&mut serde_json::Deserializer::from_str(json)).unwrap();
+                assert_iter_eq(m.iter(), [(&1, &5), (&3, &6)], $iter_eq);
+
+                let mut buf = vec![];
+                as_pairs::serialize(&m, &mut serde_json::Serializer::new(&mut buf)).unwrap();
+                assert_eq!(serde_json::from_slice::<Vec<(i32, i32)>>(&buf).unwrap().len(), 2);
+            }
+
+            #[cfg(feature = "serde")]
+            #[test]
+            fn serde_deserialize_into() {
+                let json = r#"{"foo":1,"bar":2}"#;
+                let mut m = serde_json::from_str::<$Map<String, i32>>(json).unwrap();
+                m.deserialize_into(&mut serde_json::Deserializer::from_str(
+                    r#"{"bar":3,"foo":0,"baz":4}"#,
+                ))
+                .unwrap();
+                assert_eq!(m.len(), 2);
+                assert_eq!(*m.get("foo"), 0);
+                assert_eq!(*m.get("bar"), 3);
+                assert_eq!(*m.get("baz"), 4);
+
+                assert!(m.deserialize_into(&mut serde_json::Deserializer::from_str("[]")).is_err());
+                assert_eq!(m.len(), 2);
+            }
+
+            #[cfg(all(feature = "serde", feature = "num-traits"))]
+            #[test]
+            fn serde_with_common() {
+                use serde::de::{
+                    value::SeqAccessDeserializer, Deserialize, IntoDeserializer, SeqAccess,
+                };
+                use total_maps::serde::WithCommon;
+
+                let mut m = $Map::<_, i32, ZeroCommonality>::new();
+                m.insert(1, 5);
+                let json = serde_json::to_string(&WithCommon(m)).unwrap();
+                assert_eq!(json, r#"[0,{"1":5}]"#);
+                let m = serde_json::from_str::<WithCommon<$Map<i32, i32, ZeroCommonality>>>(&json)
+                    .unwrap()
+                    .0;
+                assert_eq!(*m.get(&1), 5);
+                assert!(serde_json::from_str::<WithCommon<$Map<i32, i32, MaxCommonality>>>(&json)
+                    .is_err());
+
+                let mut m = $Map::<_, i32, MaxCommonality>::new();
+                m.insert(1, 5);
+                let json = serde_json::to_string(&WithCommon(m)).unwrap();
+                assert_eq!(json, r#"[2147483647,{"1":5}]"#);
+                assert!(serde_json::from_str::<WithCommon<$Map<i32, i32, MaxCommonality>>>(&json)
+                    .is_ok());
+                assert!(serde_json::from_str::<WithCommon<$Map<i32, i32, ZeroCommonality>>>(&json)
+                    .is_err());
+
+                // JSON can't represent NaN, so feed the pair [NaN, {"1": 5.0}] in by hand
+                struct NaNPair(usize);
+                impl<'de> SeqAccess<'de> for NaNPair {
+                    type Error = serde_json::Error;
+                    fn next_element_seed<T: DeserializeSeed<'de>>(
+                        &mut self,
+                        seed: T,
+                    ) -> Result<Option<T::Value>, Self::Error> {
+                        self.0 += 1;
+                        match self.0 {
+                            1 => seed.deserialize(f64::NAN.into_deserializer()).map(Some),
+                            2 => {
+                                let json = r#"{"1":5.0}"#;
+                                seed.deserialize(&mut serde_json::Deserializer::from_str(json))
+                                    .map(Some)
+                            }
+                            _ => Ok(None),
+                        }
+                    }
+                }
+                let m = WithCommon::<$Map<i32, f64, NaNCommonality>>::deserialize(
+                    SeqAccessDeserializer::new(NaNPair(0)),
+                )
+                .unwrap()
+                .0;
+                assert_eq!(*m.get(&1), 5.0);
+                assert!(m.get(&2).is_nan());
+                assert!(WithCommon::<$Map<i32, f64, ZeroCommonality>>::deserialize(
+                    SeqAccessDeserializer::new(NaNPair(0)),
+                )
+                .is_err());
+            }
+
+            #[cfg(feature = "rayon")]
+            #[test]
+            fn par_from_vec() {
+                let mut entries = (0..10_000).map(|i| (i % 100, i)).collect::<Vec<_>>();
+                entries.push((7, 0));
+                entries.push((1_000, 0));
+                let m = $Map::<_, _>::par_from_vec(entries);
+                assert_eq!(m.len(), 99);
+                assert!(!m.contains_key(&7));
+                assert!(!m.contains_key(&1_000));
+                assert!((0..100).filter(|&key| key != 7).all(|key| *m.get(&key) == 9_900 + key));
+            }
+
+            #[cfg(feature = "serde_json")]
+            #[test]
+            fn to_json_object() {
+                let mut m = $Map::<_, _>::new();
+                m.insert(1, "foo");
+                m.insert(2, "");
+                assert_eq!(m.to_json_object().unwrap(), serde_json::json!({ "1": "foo" }));
+
+                let mut m2 = $Map::<_, _>::new();
+                m2.insert((1, 2), "foo");
+                assert!(m2.to_json_object().is_err());
+            }
+
             #[test]
             fn map_eq() {
                 assert_eq!(
@@ -149,7 +893,7 @@ macro_rules! common {
                     [("baz", "quux"), ("foo", "bar")].into_iter().collect::<$Map<_, _>>()
                 );
 
-                let nan_map = $Map::<&str, _, NaNCommonality>::new();
+                let nan_map = $Map::<&str, f64, NaNCommonality>::new();
                 assert_ne!(nan_map, nan_map);
             }
         }
@@ -169,34 +913,1207 @@ fn hash_drain() {
     assert!(m.is_empty());
 }
 
-fn assert_iter_eq<I, J>(lhs: I, rhs: J, iter_eq: impl FnOnce(I::IntoIter, J::IntoIter) -> bool)
-where
-    I: IntoIterator,
-    I::IntoIter: ExactSizeIterator,
-    J: IntoIterator<Item = I::Item>,
-    J::IntoIter: ExactSizeIterator,
-{
-    let lhs = lhs.into_iter();
-    let rhs = rhs.into_iter();
-    assert_eq!(lhs.len(), rhs.len());
-    assert!(iter_eq(lhs, rhs));
+#[test]
+fn hash_drain_partial() {
+    let mut m = TotalHashMap::<_, _>::new();
+    assert_eq!(m.insert("foo", "bar"), "");
+    assert_eq!(m.insert("baz", "quux"), "");
+
+    let mut drain = m.drain();
+    assert!(drain.next().is_some());
+    drop(drain);
+    assert!(m.is_empty());
+    assert_eq!(m.get(&"foo"), &"");
+    assert_eq!(m.get(&"baz"), &"");
 }
 
-fn unordered_iter_eq<I, J>(lhs: I, rhs: J) -> bool
-where
-    I: IntoIterator,
-    J: IntoIterator<Item = I::Item>,
-    I::Item: Ord,
-{
-    lhs.into_iter().sorted().eq(rhs)
+#[test]
+fn hash_map_keys() {
+    let m = [(1, "foo"), (2, "bar"), (3, "")].into_iter().collect::<TotalHashMap<_, _>>();
+    let m = m.map_keys(|key| key * 10);
+    assert_iter_eq(m.iter(), [(&10, &"foo"), (&20, &"bar")], unordered_iter_eq);
+    assert_eq!(m.get(&1), &"");
+
+    let last = *m.values().last().unwrap();
+    let m = m.map_keys(|_| 0);
+    assert_eq!(m.len(), 1);
+    assert_eq!(m.get(&0), &last);
+}
+
+#[test]
+fn btree_map_keys() {
+    let m = [(1, "foo"), (2, "bar"), (3, "baz")].into_iter().collect::<TotalBTreeMap<_, _>>();
+    let m = m.map_keys(|key| 4 - key);
+    assert_iter_eq(m.iter(), [(&1, &"baz"), (&2, &"bar"), (&3, &"foo")], Iterator::eq);
+
+    let m = m.map_keys(|key| key / 2);
+    assert_iter_eq(m.iter(), [(&0, &"baz"), (&1, &"foo")], Iterator::eq);
 }
 
-struct NaNCommonality;
-impl Commonality<f64> for NaNCommonality {
-    fn common() -> f64 {
-        f64::NAN
+#[test]
+fn btree_bounds() {
+    let m = [(10, "foo"), (20, "bar")].into_iter().collect::<TotalBTreeMap<_, _>>();
+
+    assert_eq!(m.lower_bound(Bound::Included(&10)), Some((&10, &"foo")));
+    assert_eq!(m.lower_bound(Bound::Excluded(&10)), Some((&20, &"bar")));
+    assert_eq!(m.lower_bound(Bound::Included(&15)), Some((&20, &"bar")));
+    assert_eq!(m.lower_bound(Bound::Excluded(&20)), None);
+    assert_eq!(m.lower_bound(Bound::Unbounded), Some((&10, &"foo")));
+
+    assert_eq!(m.upper_bound(Bound::Included(&20)), Some((&20, &"bar")));
+    assert_eq!(m.upper_bound(Bound::Excluded(&20)), Some((&10, &"foo")));
+    assert_eq!(m.upper_bound(Bound::Included(&15)), Some((&10, &"foo")));
+    assert_eq!(m.upper_bound(Bound::Excluded(&10)), None);
+    assert_eq!(m.upper_bound(Bound::Unbounded), Some((&20, &"bar")));
+
+    assert_eq!(TotalBTreeMap::<i32, &str>::new().lower_bound(Bound::Unbounded), None);
+}
+
+#[test]
+fn btree_value_at() {
+    let m = [(10, 1.5), (20, 2.5), (30, 0.0)].into_iter().collect::<TotalBTreeMap<_, _>>();
+    assert_eq!(m.value_at(&0), &0.0);
+    assert_eq!(m.value_at(&10), &1.5);
+    assert_eq!(m.value_at(&15), &1.5);
+    assert_eq!(m.value_at(&20), &2.5);
+    assert_eq!(m.value_at(&100), &2.5);
+    assert_eq!(m.get(&15), &0.0);
+}
+
+#[test]
+fn hash_eq_across_hashers() {
+    type FixedState = BuildHasherDefault<DefaultHasher>;
+
+    let elems = [("foo", "bar"), ("baz", "quux"), ("xyzzy", "")];
+    let m1 = elems.into_iter().collect::<TotalHashMap<_, _>>();
+    let m2 =
+        elems.into_iter().rev().collect::<TotalHashMap<_, _, DefaultCommonality, FixedState>>();
+    assert_eq!(m1, m2);
+    assert_eq!(m2, m1);
+
+    let m3 =
+        elems[..1].iter().copied().collect::<TotalHashMap<_, _, DefaultCommonality, FixedState>>();
+    assert_ne!(m1, m3);
+    assert_ne!(m3, m1);
+}
+
+#[test]
+fn btree_cursor_mut() {
+    let mut m = [(1, 10), (2, 20), (3, 30), (4, 40)].into_iter().collect::<TotalBTreeMap<_, _>>();
+
+    let mut cursor = m.lower_bound_mut(Bound::Included(&2));
+    assert_eq!(cursor.key(), Some(&2));
+    *cursor.value_mut().unwrap() = 0;
+    assert_eq!(cursor.value(), Some(&0));
+    cursor.move_next();
+    assert_eq!(cursor.key(), Some(&3));
+    *cursor.value_mut().unwrap() += 1;
+    cursor.move_prev();
+    assert_eq!(cursor.key(), Some(&1));
+    cursor.move_prev();
+    assert_eq!(cursor.key(), None);
+    assert_eq!(cursor.value(), None);
+    cursor.move_prev();
+    assert_eq!(cursor.key(), Some(&4));
+    assert_eq!(cursor.remove_current(), Some((4, 40)));
+    assert_eq!(cursor.key(), None);
+    cursor.move_next();
+    assert_eq!(cursor.key(), Some(&1));
+    *cursor.value_mut().unwrap() = 0;
+    drop(cursor);
+    assert_iter_eq(m.iter(), [(&3, &31)], Iterator::eq);
+
+    let mut cursor = m.upper_bound_mut(Bound::Excluded(&3));
+    assert_eq!(cursor.key(), None);
+    cursor.move_next();
+    assert_eq!(cursor.remove_current(), Some((3, 31)));
+    drop(cursor);
+    assert!(m.is_empty());
+}
+
+#[test]
+fn hash_builder() {
+    let m = TotalHashMap::<&str, &str>::builder().build();
+    assert_eq!(m.capacity(), 0);
+    assert_eq!(m.get(&"foo"), &"");
+
+    let mut m = TotalHashMap::<&str, f64>::builder()
+        .capacity(10)
+        .commonality::<NaNCommonality>()
+        .hasher(BuildHasherDefault::<DefaultHasher>::default())
+        .build();
+    assert!(m.capacity() >= 10);
+    assert!(m.get(&"foo").is_nan());
+    m.insert("foo", 0.0);
+    m.insert("bar", f64::NAN);
+    assert_eq!(m.len(), 1);
+    assert_eq!(m.get(&"foo"), &0.0);
+}
+
+#[test]
+fn hash_with_hasher() {
+    type FixedState = BuildHasherDefault<DefaultHasher>;
+
+    let mut m =
+        TotalHashMap::<&str, i32, DefaultCommonality, _>::with_hasher(FixedState::default());
+    assert!(m.is_empty());
+    assert_eq!(m.get("foo"), &0);
+    m.insert("foo", 1);
+    let m2 = m.clone();
+    assert_eq!(m, m2);
+
+    let m = TotalHashMap::<&str, i32, DefaultCommonality, _>::with_capacity_and_hasher(
+        10,
+        FixedState::default(),
+    );
+    assert!(m.capacity() >= 10);
+    assert!(m.is_empty());
+    assert_eq!(m.hasher().hash_one("foo"), FixedState::default().hash_one("foo"));
+}
+
+#[test]
+fn hash_hash_of() {
+    let m1 = [("foo", "bar")].into_iter().collect::<TotalHashMap<_, _>>();
+    let m2 = m1.clone();
+    assert_eq!(m1.hash_of("foo"), m2.hash_of("foo"));
+    assert_eq!(m1.hash_of("xyzzy"), m2.hash_of("xyzzy"));
+
+    type FixedState = BuildHasherDefault<DefaultHasher>;
+    let m1 = TotalHashMap::<String, &str, DefaultCommonality, FixedState>::default();
+    let m2 = TotalHashMap::<String, &str, DefaultCommonality, FixedState>::default();
+    assert_eq!(m1.hash_of("foo"), m2.hash_of("foo"));
+    assert_eq!(m1.hash_of("foo"), m2.hash_of(&"foo".to_string()));
+}
+
+#[test]
+fn btree_order_by() {
+    struct CaseInsensitive;
+    impl Comparator<String> for CaseInsensitive {
+        fn compare(lhs: &String, rhs: &String) -> Ordering {
+            lhs.to_lowercase().cmp(&rhs.to_lowercase())
+        }
+    }
+
+    let mut m = TotalBTreeMapBy::<String, i32, CaseInsensitive>::new();
+    m.insert("b".to_string().into(), 1);
+    m.insert("A".to_string().into(), 2);
+    m.insert("B".to_string().into(), 3);
+    m.insert("c".to_string().into(), 0);
+
+    assert_eq!(m.len(), 2);
+    assert_eq!(m.keys().map(|key| key.as_str()).collect::<Vec<_>>(), ["A", "b"]);
+    assert_eq!(m[OrderBy::from_ref(&"a".to_string())], 2);
+    assert_eq!(m[OrderBy::from_ref(&"b".to_string())], 3);
+    assert_eq!(m[OrderBy::from_ref(&"C".to_string())], 0);
+
+    let (key, value) = m.lower_bound(Bound::Excluded(OrderBy::from_ref(&"a".to_string()))).unwrap();
+    assert_eq!((key.as_str(), *value), ("b", 3));
+}
+
+#[test]
+fn hash_capacity() {
+    let m = TotalHashMap::<&str, &str>::new();
+    assert_eq!(m.capacity(), 0);
+    assert_eq!(m.load_factor(), 0.0);
+    assert_eq!(m.memory_hint(), std::mem::size_of::<TotalHashMap<&str, &str>>());
+
+    let mut m = TotalHashMap::<_, _>::with_capacity(10);
+    assert!(m.capacity() >= 10);
+    assert_eq!(m.load_factor(), 0.0);
+    assert!(m.memory_hint() >= 10 * std::mem::size_of::<(&str, &str)>());
+
+    m.insert("foo", "bar");
+    m.insert("baz", "");
+    assert_eq!(m.load_factor(), 1.0 / m.capacity() as f64);
+
+    m.reserve(100);
+    assert!(m.capacity() >= 101);
+
+    assert!(m.try_reserve(200).is_ok());
+    assert!(m.capacity() >= 201);
+    assert!(m.try_reserve(usize::MAX).is_err());
+    assert_eq!(m.get(&"foo"), &"bar");
+
+    m.clear();
+    m.shrink_to_fit();
+    assert_eq!(m.capacity(), 0);
+    assert_eq!(m.load_factor(), 0.0);
+}
+
+#[test]
+fn btree_from_vec() {
+    let m = TotalBTreeMap::<_, _>::from_vec(vec![
+        ("foo", "a"),
+        ("bar", "b"),
+        ("foo", "c"),
+        ("baz", "d"),
+        ("baz", ""),
+        ("quux", ""),
+        ("bar", "e"),
+    ]);
+    assert_iter_eq(m.iter(), [(&"bar", &"e"), (&"foo", &"c")], Iterator::eq);
+
+    let m2 = TotalBTreeMap::<_, _>::from_vec(vec![("foo", ""), ("foo", "a")]);
+    assert_iter_eq(m2.iter(), [(&"foo", &"a")], Iterator::eq);
+}
+
+#[test]
+fn frozen_freeze_thaw() {
+    let m = [("foo", "a"), ("bar", "b"), ("baz", "c")].into_iter().collect::<TotalBTreeMap<_, _>>();
+    let frozen = m.clone().freeze();
+    assert_eq!(frozen.len(), 3);
+    assert_eq!(frozen.get(&"bar"), &"b");
+    assert_eq!(frozen[&"foo"], "a");
+    assert_eq!(frozen.get(&"quux"), &"");
+    assert!(frozen.contains_key(&"baz"));
+    assert!(!frozen.contains_key(&"quux"));
+    assert_iter_eq(frozen.iter(), [(&"bar", &"b"), (&"baz", &"c"), (&"foo", &"a")], Iterator::eq);
+    assert_eq!(frozen.clone().thaw(), m);
+
+    let hash_frozen = m.iter().collect::<TotalHashMap<_, _>>().freeze();
+    assert_eq!(hash_frozen, frozen);
+    assert!(FrozenTotalMap::<&str, &str>::default().is_empty());
+}
+
+#[test]
+fn btree_sub_range() {
+    let m = (1..=5).map(|key| (key, key * 10)).collect::<TotalBTreeMap<_, _>>();
+    let sub = m.sub_range(2..4);
+    assert_eq!(sub.len(), 2);
+    assert!(!sub.is_empty());
+    assert_eq!(sub.get(&2), &20);
+    assert_eq!(sub[&3], 30);
+    assert_eq!(sub.get(&4), &0);
+    assert_eq!(sub.get(&0), &0);
+    assert!(sub.contains_key(&3));
+    assert!(!sub.contains_key(&5));
+    assert!(sub.iter().eq([(&2, &20), (&3, &30)]));
+    assert!(sub.iter().rev().eq([(&3, &30), (&2, &20)]));
+
+    assert!(m.sub_range(6..).is_empty());
+    assert_eq!(m.sub_range(..).len(), 5);
+
+    let m =
+        ["a", "b", "c"].map(|key| (key.to_owned(), 1)).into_iter().collect::<TotalBTreeMap<_, _>>();
+    let sub = m.sub_range("b".to_owned()..);
+    assert_eq!(sub.get("b"), &1);
+    assert_eq!(sub["c"], 1);
+    assert_eq!(sub.get("a"), &0);
+    assert!(sub.contains_key("c"));
+    assert!(!sub.contains_key("a"));
+}
+
+#[test]
+#[should_panic]
+fn btree_sub_range_invalid() {
+    let m = TotalBTreeMap::<i32, i32>::new();
+    #[allow(clippy::reversed_empty_ranges)]
+    m.sub_range(3..1);
+}
+
+#[test]
+fn empty_commonality() {
+    struct NoEq;
+    let mut m = TotalHashMap::<_, Vec<NoEq>, EmptyCommonality>::new();
+    m.entry("foo").push(NoEq);
+    m.entry("bar").push(NoEq);
+    m.entry("bar").clear();
+    assert_iter_eq(m.keys(), [&"foo"], Iterator::eq);
+    assert!(m.get(&"bar").is_empty());
+
+    let mut m2 = TotalBTreeMap::<_, HashSet<i32>, EmptyCommonality>::new();
+    m2.entry("foo").insert(1);
+    m2.insert("bar", HashSet::new());
+    assert_iter_eq(m2.keys(), [&"foo"], Iterator::eq);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn btree_serde_dense_array() {
+    use total_maps::serde::{as_dense_array, from_dense_array};
+
+    let m = [(1, 5), (3, 7)].into_iter().collect::<TotalBTreeMap<usize, i32>>();
+    let json = serde_json::to_string(&as_dense_array(&m, 5)).unwrap();
+    assert_eq!(json, "[0,5,0,7,0]");
+    assert!(serde_json::to_string(&as_dense_array(&m, 3)).is_err());
+
+    let m2 = from_dense_array::<i32, DefaultCommonality>(5)
+        .deserialize(&mut serde_json::Deserializer::from_str(&json))
+        .unwrap();
+    assert_eq!(m2, m);
+    assert_eq!(m2.len(), 2);
+
+    for json in ["[0,5,0,7]", "[0,5,0,7,0,0]"] {
+        assert!(from_dense_array::<i32, DefaultCommonality>(5)
+            .deserialize(&mut serde_json::Deserializer::from_str(json))
+            .is_err());
+    }
+}
+
+#[test]
+fn hash_hash_order_independent() {
+    let entries = (0..100).map(|i| (i, i * 2)).collect::<Vec<_>>();
+    let m1 = entries.iter().copied().collect::<TotalHashMap<_, _>>();
+    let m2 = entries.iter().copied().rev().collect::<TotalHashMap<_, _>>();
+    let mut m3 = entries.iter().copied().collect::<TotalHashMap<_, _>>();
+    m3.insert(0, 1);
+    let m4 = entries.iter().copied().collect::<TotalHashMap<
+        _,
+        _,
+        DefaultCommonality,
+        BuildHasherDefault<DefaultHasher>,
+    >>();
+    let state = RandomState::new();
+    assert_eq!(state.hash_one(&m1), state.hash_one(&m2));
+    assert_eq!(state.hash_one(&m1), state.hash_one(&m4));
+    assert_ne!(state.hash_one(&m1), state.hash_one(&m3));
+
+    let set = [m1, m2, m3].into_iter().collect::<HashSet<_>>();
+    assert_eq!(set.len(), 2);
+}
+
+#[test]
+fn hash_ord() {
+    let maps = [
+        vec![],
+        vec![(1, 1)],
+        vec![(1, 2)],
+        vec![(1, 1), (2, 1)],
+        vec![(2, 1)],
+        vec![(0, 5), (3, 1)],
+    ];
+    for lhs in &maps {
+        for rhs in &maps {
+            let hash_lhs = lhs.iter().copied().collect::<TotalHashMap<_, _>>();
+            let hash_rhs = rhs.iter().copied().rev().collect::<TotalHashMap<_, _>>();
+            let btree_lhs = lhs.iter().copied().collect::<TotalBTreeMap<_, _>>();
+            let btree_rhs = rhs.iter().copied().collect::<TotalBTreeMap<_, _>>();
+            assert_eq!(hash_lhs.cmp(&hash_rhs), btree_lhs.cmp(&btree_rhs));
+            assert_eq!(hash_lhs.partial_cmp(&hash_rhs), btree_lhs.partial_cmp(&btree_rhs));
+        }
+    }
+
+    let nan_map = TotalHashMap::<&str, f64, NaNCommonality>::new();
+    assert_eq!(nan_map.partial_cmp(&nan_map), None);
+}
+
+#[cfg(feature = "num-traits")]
+#[test]
+fn sparse_aliases() {
+    use total_maps::nonzero::{sparse_vec, SparseF64BTreeMap, SparseI64Map};
+
+    let mut m = SparseI64Map::new();
+    m.insert("foo", 3);
+    m.insert("bar", 0);
+    assert_iter_eq(m.keys(), [&"foo"], Iterator::eq);
+
+    let mut m2 = SparseF64BTreeMap::new();
+    *m2.entry(1) += 0.5;
+    *m2.entry(1) -= 0.5;
+    assert!(m2.is_empty());
+
+    let v = sparse_vec([0, 4, 0, 0, 7]);
+    assert_iter_eq(v.iter(), [(&1, &4), (&4, &7)], Iterator::eq);
+    assert_eq!(v.get(&100), &0);
+}
+
+#[test]
+fn commonality_cheap() {
+    fn is_cheap<C: Commonality<V>, V>() -> bool {
+        C::CHEAP
+    }
+    assert!(!is_cheap::<DefaultCommonality, String>());
+    assert!(!is_cheap::<EmptyCommonality, Vec<i32>>());
+    #[cfg(feature = "num-traits")]
+    assert!(is_cheap::<ZeroCommonality, i32>());
+}
+
+#[test]
+fn as_ref_inner() {
+    fn hash_len<M: AsRef<HashMap<&'static str, i32>>>(map: M) -> usize {
+        map.as_ref().len()
+    }
+    fn btree_first<M: AsRef<BTreeMap<&'static str, i32>>>(map: &M) -> Option<&'static str> {
+        map.as_ref().keys().next().copied()
+    }
+
+    let m = [("foo", 1), ("bar", 0)].into_iter().collect::<TotalHashMap<_, _>>();
+    assert_eq!(hash_len(&m), 1);
+    assert_eq!(m.as_hash_map().get("foo"), Some(&1));
+
+    let m2 = [("foo", 1), ("bar", 2)].into_iter().collect::<TotalBTreeMap<_, _>>();
+    assert_eq!(btree_first(&m2), Some("bar"));
+    assert_eq!(m2.as_btree_map().len(), 2);
+}
+
+#[test]
+fn hash_strict() {
+    let mut m = TotalHashMap::<_, i32>::strict();
+    assert_eq!(m.insert("foo", 1), 0);
+    *m.entry("foo") += 1;
+    *m.entry("bar") += 3;
+    assert_eq!(m.get(&"foo"), &2);
+    assert_eq!(m.len(), 2);
+
+    let err = m.try_insert_strict("foo", 0).unwrap_err();
+    assert_eq!((err.key, err.value), ("foo", 0));
+    assert_eq!(m.get(&"foo"), &2);
+
+    assert_eq!(m.remove(&"foo"), 2);
+    assert_iter_eq(m.keys(), [&"bar"], Iterator::eq);
+
+    assert_eq!(*m.entry("quux"), 0);
+    drop(m.entry("quux"));
+    drop(m.entry("bar"));
+    assert_iter_eq(m.keys(), [&"bar"], Iterator::eq);
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| m.insert("baz", 0)));
+    assert!(result.is_err());
+    let result = panic::catch_unwind(AssertUnwindSafe(|| *m.entry("bar") = 0));
+    assert!(result.is_err());
+    assert!(m.is_empty());
+    assert!(m.is_canonical());
+}
+
+#[test]
+fn iterator_traits() {
+    fn exact_fused<I: ExactSizeIterator + FusedIterator>() {}
+    fn fused<I: FusedIterator>() {}
+    fn fused_debug<I: FusedIterator + std::fmt::Debug>() {}
+
+    {
+        use total_maps::hash_map::*;
+        exact_fused::<Keys<'static, i32, i32>>();
+        exact_fused::<IntoKeys<i32, i32>>();
+        exact_fused::<Values<'static, i32, i32>>();
+        exact_fused::<IntoValues<i32, i32>>();
+        exact_fused::<Iter<'static, i32, i32>>();
+        exact_fused::<IntoIter<i32, i32>>();
+        exact_fused::<Drain<'static, i32, i32>>();
+        fused_debug::<ExtractIf<'static, i32, i32, fn(&i32, &mut i32) -> bool>>();
+    }
+    {
+        use total_maps::btree_map::*;
+        exact_fused::<Keys<'static, i32, i32>>();
+        exact_fused::<IntoKeys<i32, i32>>();
+        exact_fused::<Values<'static, i32, i32>>();
+        exact_fused::<IntoValues<i32, i32>>();
+        exact_fused::<Iter<'static, i32, i32>>();
+        exact_fused::<IntoIter<i32, i32>>();
+        exact_fused::<Drain<'static, i32, i32>>();
+        // BTreeMap's own Range doesn't know its length
+        fused::<Range<'static, i32, i32>>();
+        fused_debug::<ExtractIf<'static, i32, i32, fn(&i32, &mut i32) -> bool>>();
+    }
+    exact_fused::<total_maps::frozen::Iter<'static, i32, i32>>();
+}
+
+#[test]
+fn iterator_defaults() {
+    fn empty<I: Default + ExactSizeIterator>() {
+        assert_eq!(I::default().len(), 0);
+    }
+
+    {
+        use total_maps::hash_map::*;
+        empty::<Keys<'static, i32, i32>>();
+        empty::<IntoKeys<i32, i32>>();
+        empty::<Values<'static, i32, i32>>();
+        empty::<IntoValues<i32, i32>>();
+        empty::<Iter<'static, i32, i32>>();
+        empty::<IntoIter<i32, i32>>();
+        empty::<Drain<'static, i32, i32>>();
+    }
+    {
+        use total_maps::btree_map::*;
+        empty::<Keys<'static, i32, i32>>();
+        empty::<IntoKeys<i32, i32>>();
+        empty::<Values<'static, i32, i32>>();
+        empty::<IntoValues<i32, i32>>();
+        empty::<Iter<'static, i32, i32>>();
+        empty::<IntoIter<i32, i32>>();
+        empty::<Drain<'static, i32, i32>>();
+    }
+    empty::<total_maps::frozen::Iter<'static, i32, i32>>();
+}
+
+#[test]
+fn support() {
+    let m = [(3, "c"), (1, "a"), (2, ""), (4, "d")].into_iter().collect::<TotalHashMap<_, _>>();
+    assert_eq!(m.support_sorted(), [&1, &3, &4]);
+    assert!(unordered_iter_eq(m.support(), [&1, &3, &4]));
+
+    let m = m.iter().collect::<TotalBTreeMap<_, _>>();
+    assert_eq!(m.support(), [&1, &3, &4]);
+}
+
+#[test]
+fn lru_eviction() {
+    let evicted = Arc::new(Mutex::new(Vec::new()));
+    let mut m = BoundedTotalHashMap::<_, i32>::new(2);
+    m.on_evict({
+        let evicted = evicted.clone();
+        move |key, value| evicted.lock().unwrap().push((key, value))
+    });
+    m.insert("a", 1);
+    m.insert("b", 2);
+    assert_eq!(m.get("a"), &1);
+    m.insert("c", 3);
+    assert_eq!(*evicted.lock().unwrap(), [("b", 2)]);
+    assert_eq!(m.peek("b"), &0);
+    assert_eq!(m.len(), 2);
+
+    assert_eq!(m.peek("a"), &1);
+    m.insert("d", 4);
+    assert_eq!(*evicted.lock().unwrap(), [("b", 2), ("a", 1)]);
+    assert_eq!(m.get("a"), &0);
+
+    assert_eq!(m.insert("c", 0), 3);
+    assert_eq!(m.remove("d"), 4);
+    assert!(m.is_empty());
+    assert_eq!(evicted.lock().unwrap().len(), 2);
+
+    m.insert("e", 5);
+    let m = thread::spawn(move || m).join().unwrap();
+    assert_eq!(m.peek("e"), &5);
+}
+
+#[test]
+fn hash_get_mut() {
+    let mut m = TotalHashMap::<String, i32>::new();
+    m.insert("foo".into(), 1);
+    assert!(m.get_mut("bar").is_none());
+    assert!(!m.contains_key("bar"));
+
+    *m.get_mut("foo").unwrap() += 2;
+    assert_eq!(m.get("foo"), &3);
+
+    *m.get_mut("foo").unwrap() = 0;
+    assert_eq!(m.get("foo"), &0);
+    assert!(!m.contains_key("foo"));
+    assert!(m.get_mut("foo").is_none());
+
+    m.insert("bar".into(), 4);
+    let value = m.get_mut("bar").unwrap();
+    assert_eq!(format!("{value:?}"), r#"RefMut("bar", 4)"#);
+    mem::forget(value);
+    assert_eq!(m.get("bar"), &4);
+    let mut value = m.get_mut("bar").unwrap();
+    *value = 0;
+    mem::forget(value);
+    assert!(!m.is_canonical());
+    m.repair();
+    assert!(m.is_empty());
+}
+
+#[test]
+fn btree_get_mut() {
+    let mut m = TotalBTreeMap::<String, i32>::new();
+    m.insert("foo".into(), 1);
+    m.insert("bar".into(), 2);
+    assert!(m.get_mut("baz").is_none());
+
+    *m.get_mut("foo").unwrap() += 2;
+    assert_eq!(m.get("foo"), &3);
+
+    *m.get_mut("foo").unwrap() = 0;
+    assert!(!m.contains_key("foo"));
+    assert_eq!(m.len(), 1);
+
+    let value = m.get_mut("bar").unwrap();
+    assert_eq!(format!("{value:?}"), r#"RefMut("bar", 2)"#);
+    mem::forget(value);
+    assert_eq!(m.get("bar"), &2);
+    let mut value = m.get_mut("bar").unwrap();
+    *value = 0;
+    mem::forget(value);
+    assert!(!m.is_canonical());
+    m.repair();
+    assert!(m.is_empty());
+}
+
+#[test]
+fn hash_retain() {
+    let mut m = (1..=6).map(|i| (i, i * 10)).collect::<TotalHashMap<_, _>>();
+    m.retain(|&key, value| {
+        if key == 3 {
+            *value = 0;
+        } else {
+            *value += 1;
+        }
+        key != 6
+    });
+    assert_iter_eq(m.iter(), [(&1, &11), (&2, &21), (&4, &41), (&5, &51)], unordered_iter_eq);
+    assert_eq!(m.get(&3), &0);
+}
+
+#[test]
+fn btree_retain() {
+    let mut m =
+        [(3, 1), (1, 2), (5, -12), (4, 4), (2, 5)].into_iter().collect::<TotalBTreeMap<_, _>>();
+    let mut visited = Vec::new();
+    let mut total = 0;
+    m.retain(|&key, value| {
+        visited.push(key);
+        total += *value;
+        *value = total;
+        key != 4
+    });
+    assert_eq!(visited, [1, 2, 3, 4, 5]);
+    assert_iter_eq(m.iter(), [(&1, &2), (&2, &7), (&3, &8)], Iterator::eq);
+    assert!(!m.contains_key(&5));
+}
+
+#[test]
+fn btree_range() {
+    let mut m = (1..=6).map(|i| (i.to_string(), i)).collect::<TotalBTreeMap<_, _>>();
+    m.insert("3".into(), 0);
+    let range = m.range::<str, _>((Bound::Included("2"), Bound::Excluded("5")));
+    assert!(range.eq([(&"2".into(), &2), (&"4".into(), &4)]));
+    let range = m.range::<str, _>((Bound::Included("2"), Bound::Unbounded));
+    assert!(range.rev().map(|(_, &value)| value).eq([6, 5, 4, 2]));
+    assert_eq!(m.range("7".to_owned()..).next(), None);
+}
+
+#[test]
+fn btree_range_mut() {
+    let mut m = (1..=6).map(|i| (i, i * 10)).collect::<TotalBTreeMap<_, _>>();
+    m.insert(3, 0);
+    let mut range = m.range_mut(2..=5);
+    let mut visited = Vec::new();
+    while let Some((&key, value)) = range.next() {
+        visited.push(key);
+        *value = if key == 4 { 0 } else { *value + 1 };
+    }
+    assert!(range.next().is_none());
+    drop(range);
+    assert_eq!(visited, [2, 4, 5]);
+    assert_iter_eq(m.iter(), [(&1, &10), (&2, &21), (&5, &51), (&6, &60)], Iterator::eq);
+
+    let mut range = m.range_mut(5..);
+    *range.next().unwrap().1 = 0;
+    drop(range);
+    assert!(!m.contains_key(&5));
+}
+
+#[test]
+fn btree_first_last() {
+    let mut m = TotalBTreeMap::<i32, i32>::new();
+    m.insert(2, 0);
+    assert_eq!(m.first_key_value(), None);
+    assert_eq!(m.last_key_value(), None);
+
+    m.extend([(3, 30), (1, 10), (2, 20)]);
+    assert_eq!(m.first_key_value(), Some((&1, &10)));
+    assert_eq!(m.last_key_value(), Some((&3, &30)));
+}
+
+#[test]
+fn btree_pop_first_last() {
+    let mut m = [(3, 30), (1, 10), (2, 20), (4, 0)].into_iter().collect::<TotalBTreeMap<_, _>>();
+    assert_eq!(m.pop_first(), Some((1, 10)));
+    assert_eq!(m.pop_last(), Some((3, 30)));
+    assert_eq!(m.get(&3), &0);
+    assert_eq!(m.pop_last(), Some((2, 20)));
+    assert_eq!(m.pop_first(), None);
+    assert_eq!(m.pop_last(), None);
+}
+
+#[test]
+fn btree_first_last_entry() {
+    let mut m = TotalBTreeMap::<i32, i32>::new();
+    assert!(m.first_entry().is_none());
+    assert!(m.last_entry().is_none());
+
+    m.extend([(3, 30), (1, 10), (2, 20)]);
+    *m.first_entry().unwrap() += 1;
+    assert_eq!(m.get(&1), &11);
+    *m.last_entry().unwrap() = 0;
+    assert!(!m.contains_key(&3));
+    assert_eq!(m.last_key_value(), Some((&2, &20)));
+}
+
+#[test]
+fn btree_split_off() {
+    let mut m = (1..=5).map(|i| (i, i * 10)).collect::<TotalBTreeMap<_, _>>();
+    let upper = m.split_off(&3);
+    assert_iter_eq(m.iter(), [(&1, &10), (&2, &20)], Iterator::eq);
+    assert_iter_eq(upper.iter(), [(&3, &30), (&4, &40), (&5, &50)], Iterator::eq);
+    assert_eq!(m.get(&4), &0);
+    assert_eq!(upper.get(&1), &0);
+    assert_eq!(upper.get(&10), &0);
+}
+
+#[test]
+fn btree_drain() {
+    let mut m = (1..=4).map(|i| (i, i * 10)).collect::<TotalBTreeMap<_, _>>();
+    let mut drain = m.drain();
+    assert_eq!(drain.next_back(), Some((4, 40)));
+    assert_iter_eq(drain, [(1, 10), (2, 20), (3, 30)], Iterator::eq);
+    assert!(m.is_empty());
+
+    m.insert(5, 50);
+    drop(m.drain());
+    assert_eq!(m.get(&5), &0);
+}
+
+#[test]
+fn hash_extract_if() {
+    let mut m = (1..=6).map(|i| (i, i * 10)).collect::<TotalHashMap<_, _>>();
+    let extracted = m
+        .extract_if(|&key, value| {
+            if key == 5 {
+                *value = 0;
+            }
+            key % 2 == 0
+        })
+        .collect::<Vec<_>>();
+    assert!(unordered_iter_eq(extracted, [(2, 20), (4, 40), (6, 60)]));
+    assert_iter_eq(m.iter(), [(&1, &10), (&3, &30)], unordered_iter_eq);
+    assert!(!m.contains_key(&5));
+
+    let mut iter = m.extract_if(|_, _| true);
+    assert!(iter.next().is_some());
+    drop(iter);
+    assert_eq!(m.len(), 1);
+}
+
+#[test]
+fn hash_get_many_mut() {
+    let mut m = (1..=4).map(|i| (i, i * 10)).collect::<TotalHashMap<_, _>>();
+    assert!(m.get_many_mut([&1, &5]).is_none());
+    assert!(m.get_many_mut([&1, &2, &1]).is_none());
+    assert_eq!(m.len(), 4);
+
+    let mut many = m.get_many_mut([&3, &1]).unwrap();
+    assert_eq!(many.keys(), [&3, &1]);
+    let [three, one] = many.values_mut();
+    mem::swap(three, one);
+    *one = 0;
+    drop(many);
+    assert_iter_eq(m.iter(), [(&2, &20), (&3, &10), (&4, &40)], unordered_iter_eq);
+}
+
+#[test]
+fn hash_pop() {
+    let mut m = (1..=3).map(|i| (i, i * 10)).collect::<TotalHashMap<_, _>>();
+    let mut popped = Vec::new();
+    while let Some((key, value)) = m.pop() {
+        assert_eq!(value, key * 10);
+        assert_eq!(m.get(&key), &0);
+        popped.push(key);
+    }
+    popped.sort();
+    assert_eq!(popped, [1, 2, 3]);
+    assert!(m.is_empty());
+}
+
+#[test]
+fn try_from_inner() {
+    let inner = HashMap::from([("foo", 1), ("bar", 2)]);
+    let m = TotalHashMap::<_, _>::try_from_hash_map(inner).unwrap();
+    assert_eq!(m.len(), 2);
+    let inner = HashMap::from([("foo", 1), ("bar", 0)]);
+    let err = TotalHashMap::<_, _>::try_from_hash_map(inner.clone()).unwrap_err();
+    assert_eq!(err, CommonValuePresent { keys: vec!["bar"], map: inner });
+    assert_eq!(err.to_string(), "map contains 1 common values");
+
+    let inner = BTreeMap::from([("foo", 0), ("bar", 2), ("baz", 0)]);
+    let err = TotalBTreeMap::<_, _>::try_from_btree_map(inner.clone()).unwrap_err();
+    assert_eq!(err.keys, ["baz", "foo"]);
+    assert_eq!(err.map, inner);
+    let inner = BTreeMap::from([("bar", 2)]);
+    assert!(TotalBTreeMap::<_, _>::try_from_btree_map(inner).is_ok());
+}
+
+#[test]
+fn from_inner() {
+    let inner = HashMap::from([("foo", 1), ("bar", 0), ("baz", 3)]);
+    let m = TotalHashMap::<_, _>::from(inner);
+    assert!(m.is_canonical());
+    assert_iter_eq(m.iter(), [(&"baz", &3), (&"foo", &1)], unordered_iter_eq);
+
+    let inner = BTreeMap::from([("foo", 0), ("bar", 2), ("baz", 0)]);
+    let m = TotalBTreeMap::<_, _>::from(inner);
+    assert!(m.is_canonical());
+    assert_iter_eq(m.iter(), [(&"bar", &2)], Iterator::eq);
+}
+
+#[test]
+fn into_inner() {
+    let m = [("foo", 1), ("bar", 0)].into_iter().collect::<TotalHashMap<_, _>>();
+    assert_eq!(m.into_hash_map(), HashMap::from([("foo", 1)]));
+
+    let m = [("foo", 0), ("bar", 2)].into_iter().collect::<TotalBTreeMap<_, _>>();
+    assert_eq!(m.into_btree_map(), BTreeMap::from([("bar", 2)]));
+}
+
+#[test]
+fn btree_extract_if() {
+    let mut m = (1..=6).map(|i| (i, i * 10)).collect::<TotalBTreeMap<_, _>>();
+    let extracted = m
+        .extract_if(|&key, value| {
+            if key == 5 {
+                *value = 0;
+            }
+            key % 2 == 0
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(extracted, [(2, 20), (4, 40), (6, 60)]);
+    assert_iter_eq(m.iter(), [(&1, &10), (&3, &30)], Iterator::eq);
+
+    let mut m = (1..=6).map(|i| (i, i * 10)).collect::<TotalBTreeMap<_, _>>();
+    let mut iter = m.extract_if(|_, _| true);
+    assert_eq!(iter.next(), Some((1, 10)));
+    assert_eq!(iter.next(), Some((2, 20)));
+    drop(iter);
+    assert_iter_eq(m.iter(), [(&3, &30), (&4, &40), (&5, &50), (&6, &60)], Iterator::eq);
+}
+
+#[cfg(feature = "num-traits")]
+#[test]
+fn btree_const_empty() {
+    const EMPTY: TotalBTreeMap<&str, i32, ZeroCommonality> = TotalBTreeMap::empty();
+    let mut m = EMPTY;
+    assert!(m.is_empty());
+    assert_eq!(m.get("foo"), &0);
+    m.insert("foo", 3);
+    assert_eq!(m.get("foo"), &3);
+    assert!(EMPTY.is_empty());
+}
+
+#[test]
+fn sharded_concurrent_add() {
+    let m = ShardedTotalHashMap::<u32, i64>::with_shards(4);
+    thread::scope(|scope| {
+        for _ in 0..8 {
+            scope.spawn(|| {
+                for i in 0..1_000 {
+                    m.add(i % 10, 1);
+                }
+            });
+        }
+    });
+    assert_eq!(m.len(), 10);
+    assert!((0..10).all(|key| m.get(&key) == 800));
+
+    assert_eq!(m.insert(3, 0), 800);
+    assert_eq!(m.remove(&4), 800);
+    assert_eq!(m.update(5, |value| mem::replace(value, 0)), 800);
+    assert!(!m.contains_key(&3));
+    assert_eq!(m.len(), 7);
+
+    let m = TotalHashMap::from(m);
+    assert_eq!(m.len(), 7);
+    assert_eq!(m.get(&0), &800);
+}
+
+#[cfg(feature = "rkyv")]
+#[test]
+fn hash_rkyv_round_trip() {
+    use rkyv::{rancor::Error, string::ArchivedString};
+    use total_maps::rkyv::ArchivedTotalHashMap;
+
+    let m = [("foo".to_owned(), 1), ("bar".to_owned(), 0), ("baz".to_owned(), 3)]
+        .into_iter()
+        .collect::<TotalHashMap<_, i32>>();
+    let bytes = rkyv::to_bytes::<Error>(&m).unwrap();
+    let archived =
+        rkyv::access::<ArchivedTotalHashMap<ArchivedString, rkyv::Archived<i32>>, Error>(&bytes)
+            .unwrap();
+    assert_eq!(archived.len(), 2);
+    assert_eq!(*archived.get("foo"), 1);
+    assert_eq!(*archived.get("bar"), 0);
+    assert!(!archived.contains_key("bar"));
+    assert_eq!(*archived.common(), 0);
+
+    let m2 = rkyv::deserialize::<TotalHashMap<String, i32>, Error>(archived).unwrap();
+    assert_eq!(m2, m);
+}
+
+#[cfg(feature = "rkyv")]
+#[test]
+fn btree_rkyv_round_trip() {
+    use rkyv::rancor::Error;
+    use total_maps::rkyv::ArchivedTotalBTreeMap;
+
+    let m = [(1, 10), (2, 0), (3, 30)].into_iter().collect::<TotalBTreeMap<u32, i32>>();
+    let bytes = rkyv::to_bytes::<Error>(&m).unwrap();
+    let archived = rkyv::access::<
+        ArchivedTotalBTreeMap<rkyv::Archived<u32>, rkyv::Archived<i32>>,
+        Error,
+    >(&bytes)
+    .unwrap();
+    assert_eq!(archived.len(), 2);
+    assert_eq!(*archived.get(&rkyv::Archived::<u32>::from_native(3)), 30);
+    assert_eq!(*archived.get(&rkyv::Archived::<u32>::from_native(4)), 0);
+
+    let m2 = rkyv::deserialize::<TotalBTreeMap<u32, i32>, Error>(archived).unwrap();
+    assert_eq!(m2, m);
+}
+
+#[cfg(feature = "proptest")]
+proptest::proptest! {
+    #[test]
+    fn proptest_arbitrary_hash(m in proptest::arbitrary::any::<TotalHashMap<u8, bool>>()) {
+        proptest::prop_assert!(m.is_canonical());
+        proptest::prop_assert!(m.values().all(|&value| value));
+    }
+
+    #[test]
+    fn proptest_arbitrary_btree(
+        m in proptest::arbitrary::any_with::<TotalBTreeMap<u8, i8>>(((3..=5).into(), (), ())),
+    ) {
+        proptest::prop_assert!(m.is_canonical());
+        proptest::prop_assert!((3..=5).contains(&m.len()));
+    }
+
+    #[test]
+    fn proptest_custom_strategy(
+        m in total_maps::proptest::total_btree_map::<_, _, DefaultCommonality>(0..4u8, 0..2u8, 2),
+    ) {
+        proptest::prop_assert_eq!(m.len(), 2);
+        proptest::prop_assert!(m.values().all(|&value| value == 1));
+    }
+}
+
+#[cfg(feature = "quickcheck")]
+#[test]
+fn quickcheck_arbitrary() {
+    use quickcheck::{Arbitrary, Gen};
+
+    let mut g = Gen::new(20);
+    for _ in 0..20 {
+        let m = TotalHashMap::<u8, bool>::arbitrary(&mut g);
+        assert!(m.is_canonical());
+        let m = TotalBTreeMap::<u8, i8>::arbitrary(&mut g);
+        assert!(m.is_canonical());
+    }
+
+    let m = [(1, 3), (2, 1), (5, 2)].into_iter().collect::<TotalBTreeMap<u8, i8>>();
+    let shrunk = m.shrink().collect::<Vec<_>>();
+    assert!(!shrunk.is_empty());
+    assert!(shrunk.iter().all(|m| m.is_canonical()));
+    assert!(shrunk.iter().any(|m| m.len() < 3));
+    assert!(shrunk.iter().any(|m| m.len() == 3 && m.values().any(|&value| value < 2)));
+    assert!(shrunk.contains(&[(2, 1), (5, 2)].into_iter().collect()));
+}
+
+#[test]
+fn dyn_map() {
+    let sentinel = -1;
+    let mut m = TotalHashMapDyn::new_with_common(sentinel, move |&value| value == sentinel);
+    assert_eq!(m.get(&"foo"), &-1);
+    assert_eq!(m.insert("foo", 3), -1);
+    assert_eq!(m.insert("bar", 0), -1);
+    assert_eq!(m.insert("baz", -1), -1);
+    assert_eq!(m.len(), 2);
+    assert!(!m.contains_key("baz"));
+    assert_eq!(m["bar"], 0);
+    assert_eq!(m.remove("bar"), 0);
+    assert_iter_eq(m.iter(), [(&"foo", &3)], unordered_iter_eq);
+
+    *m.entry("qux") += 5;
+    assert_eq!(m["qux"], 4);
+    *m.entry("foo") = -1;
+    assert!(!m.contains_key("foo"));
+    let entry = m.entry("quux");
+    assert_eq!(format!("{entry:?}"), r#"Entry("quux", -1)"#);
+    drop(entry);
+    assert_iter_eq(m.keys(), [&"qux"], unordered_iter_eq);
+
+    let m = thread::spawn(move || m).join().unwrap();
+    thread::scope(|scope| {
+        scope.spawn(|| assert_eq!(m["qux"], 4));
+    });
+}
+
+#[test]
+#[should_panic]
+fn dyn_map_uncommon_common() {
+    TotalHashMapDyn::<&str, i32>::new_with_common(1, |&value| value == 0);
+}
+
+#[test]
+fn none_commonality() {
+    // NotEq implements neither PartialEq nor Default
+    #[derive(Debug)]
+    struct NotEq(i32);
+
+    let mut m = SparseHashMap::<&str, NotEq>::new();
+    assert!(m.get("foo").is_none());
+    m.insert("foo", Some(NotEq(1)));
+    m.insert("bar", None);
+    assert_eq!(m.len(), 1);
+    assert_eq!(m.get("foo").as_ref().unwrap().0, 1);
+    *m.entry("foo") = None;
+    assert!(m.is_empty());
+
+    let mut m = SparseBTreeMap::<i32, NotEq>::new();
+    m.entry(3).get_or_insert(NotEq(0)).0 += 4;
+    assert_eq!(m.get(&3).as_ref().unwrap().0, 4);
+    const { assert!(<NoneCommonality as Commonality<Option<NotEq>>>::CHEAP) };
+}
+
+#[test]
+fn bool_commonalities() {
+    let mut m = TotalBTreeMap::<i32, bool, FalseCommonality>::new();
+    m.extend([(1, true), (2, false), (3, true)]);
+    assert_iter_eq(m.keys(), [&1, &3], Iterator::eq);
+    assert!(!m[&2]);
+
+    let mut m = TotalBTreeMap::<i32, bool, TrueCommonality>::new();
+    m.extend([(1, true), (2, false), (3, true)]);
+    assert_iter_eq(m.keys(), [&2], Iterator::eq);
+    assert!(m[&4]);
+}
+
+#[cfg(feature = "derive")]
+#[test]
+fn derive_commonality() {
+    #[derive(Commonality, Debug)]
+    enum Cell {
+        #[common]
+        Empty,
+        Wall,
+        Item(u32),
+    }
+    const { assert!(<CellCommonality as Commonality<Cell>>::CHEAP) };
+    assert!(CellCommonality::is_common(&Cell::Empty));
+    assert!(!CellCommonality::is_common(&Cell::Item(0)));
+
+    let mut grid = TotalHashMap::<(i32, i32), Cell, CellCommonality>::new();
+    grid.insert((0, 0), Cell::Wall);
+    grid.insert((0, 1), Cell::Item(3));
+    grid.insert((0, 2), Cell::Empty);
+    assert_eq!(grid.len(), 2);
+    assert!(matches!(grid[&(0, 2)], Cell::Empty));
+    assert!(matches!(grid[&(0, 1)], Cell::Item(3)));
+
+    #[derive(Commonality, Debug, PartialEq)]
+    #[common(Score(-1))]
+    struct Score(i32);
+    assert!(ScoreCommonality::is_common(&Score(-1)));
+    assert!(!ScoreCommonality::is_common(&Score(0)));
+
+    let mut scores = TotalBTreeMap::<&str, Score, ScoreCommonality>::new();
+    scores.insert("a", Score(0));
+    scores.insert("b", Score(-1));
+    assert_eq!(scores.len(), 1);
+    assert_eq!(scores["c"], Score(-1));
+}
+
+#[cfg(feature = "num-traits")]
+#[test]
+fn pair_commonality() {
+    type C = PairCommonality<ZeroCommonality, EmptyCommonality>;
+    assert_eq!(C::common(), (0, String::new()));
+    assert!(!C::is_common(&(1, String::new())));
+    assert!(!C::is_common(&(0, "a".to_owned())));
+
+    let mut m = TotalHashMap::<&str, (i32, String), C>::new();
+    m.insert("a", (1, String::new()));
+    m.insert("b", (0, "b".to_owned()));
+    m.insert("c", (0, String::new()));
+    assert_eq!(m.len(), 2);
+    assert_eq!(m["c"], (0, String::new()));
+    m.entry("a").0 = 0;
+    assert_eq!(m.len(), 1);
+    assert_iter_eq(m.keys(), [&"b"], Iterator::eq);
+}
+
+#[test]
+fn threshold_commonality() {
+    struct Epsilon;
+    impl Threshold for Epsilon {
+        const EPSILON: f64 = 0.25;
+    }
+    type C = ThresholdCommonality<Epsilon>;
+    assert!(<C as Commonality<f64>>::is_common(&-0.25));
+    assert!(!<C as Commonality<f64>>::is_common(&0.5));
+    assert!(!<C as Commonality<f64>>::is_common(&f64::NAN));
+    assert!(<C as Commonality<f32>>::is_common(&0.125));
+
+    let mut m = TotalBTreeMap::<i32, f64, C>::new();
+    m.extend([(1, 0.1), (2, -1.0), (3, 0.0), (4, 0.3)]);
+    assert_iter_eq(m.keys(), [&2, &4], Iterator::eq);
+    assert_eq!(m[&1], 0.0);
+    *m.entry(4) -= 0.2;
+    assert_iter_eq(m.keys(), [&2], Iterator::eq);
+}
+
+#[test]
+fn nan_commonality() {
+    assert!(<NaNCommonality as Commonality<f32>>::common().is_nan());
+    assert!(!<NaNCommonality as Commonality<f32>>::is_common(&0.0));
+
+    let mut m = TotalBTreeMap::<i32, f32, NaNCommonality>::new();
+    m.extend([(1, 1.0), (2, f32::NAN), (3, 0.0)]);
+    assert_iter_eq(m.keys(), [&1, &3], Iterator::eq);
+    assert!(m[&2].is_nan());
+    assert_ne!(m, m);
+}
+
+#[cfg(feature = "num-traits")]
+#[test]
+fn bounded_commonalities() {
+    assert_eq!(<MinCommonality as Commonality<u8>>::common(), 0);
+    assert_eq!(<MaxCommonality as Commonality<i16>>::common(), i16::MAX);
+
+    let mut maxima = AboveMinBTreeMap::<&str, i32>::new();
+    for (key, value) in [("a", 3), ("b", i32::MIN), ("a", 7), ("a", 5)] {
+        let mut max = maxima.entry(key);
+        *max = (*max).max(value);
+    }
+    assert_iter_eq(maxima.iter(), [(&"a", &7)], Iterator::eq);
+    assert_eq!(maxima["b"], i32::MIN);
+
+    let mut minima = BelowMaxHashMap::<&str, u64>::new();
+    minima.insert("a", 3);
+    minima.insert("b", u64::MAX);
+    assert_eq!(minima.len(), 1);
+    assert_eq!(minima["c"], u64::MAX);
+    assert_eq!(TotalBTreeMap::<i32, f64, MinCommonality>::new()[&0], f64::MIN);
+}
+
+fn assert_iter_eq<I, J>(lhs: I, rhs: J, iter_eq: impl FnOnce(I::IntoIter, J::IntoIter) -> bool)
+where
+    I: IntoIterator,
+    I::IntoIter: ExactSizeIterator,
+    J: IntoIterator<Item = I::Item>,
+    J::IntoIter: ExactSizeIterator,
+{
+    let lhs = lhs.into_iter();
+    let rhs = rhs.into_iter();
+    assert_eq!(lhs.len(), rhs.len());
+    assert!(iter_eq(lhs, rhs));
+}
+
+fn unordered_iter_eq<I, J>(lhs: I, rhs: J) -> bool
+where
+    I: IntoIterator,
+    J: IntoIterator<Item = I::Item>,
+    I::Item: Ord,
+{
+    lhs.into_iter().sorted().eq(rhs)
+}
+
+thread_local!(static COMMON_CALLS: Cell<usize> = const { Cell::new(0) });
+
+// Counts the calls to `common()`, across all `CountingCommonality` types, on the current thread
+struct CountingCommonality<const CHEAP: bool>;
+impl<const CHEAP: bool> CountingCommonality<CHEAP> {
+    fn calls() -> usize {
+        COMMON_CALLS.with(Cell::get)
+    }
+}
+impl<const CHEAP: bool> Commonality<i32> for CountingCommonality<CHEAP> {
+    const CHEAP: bool = CHEAP;
+    fn common() -> i32 {
+        COMMON_CALLS.with(|calls| calls.set(calls.get() + 1));
+        0
     }
-    fn is_common(value: &f64) -> bool {
-        value.is_nan()
+    fn is_common(value: &i32) -> bool {
+        *value == 0
     }
 }
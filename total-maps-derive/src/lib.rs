@@ -0,0 +1,119 @@
+//! Provides the `Commonality` derive macro for [total-maps](https://docs.rs/total-maps). Enable the
+//! `derive` feature of total-maps to use it, rather than depending on this crate directly.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, Attribute, Data, DeriveInput, Error, Expr, Fields};
+
+/// Derives a [commonality](https://docs.rs/total-maps/latest/total_maps/trait.Commonality.html)
+/// for a type.
+///
+/// The macro generates a zero-sized marker type, named after the type with a `Commonality`
+/// suffix (for example, `Color` gets `ColorCommonality`), with the same visibility as the type.
+/// The marker type implements `Commonality` for the type.
+///
+/// On an enum, mark exactly one unit variant with `#[common]`; that variant is the *common* value,
+/// and values are compared by matching the variant, so the enum needn't implement [PartialEq]:
+///
+/// ```
+/// use total_maps::{Commonality, TotalHashMap};
+///
+/// #[derive(Commonality, Debug)]
+/// enum Color {
+///     Red,
+///     #[common]
+///     Transparent,
+///     Rgb(u8, u8, u8),
+/// }
+///
+/// let mut pixels = TotalHashMap::<(u32, u32), Color, ColorCommonality>::new();
+/// pixels.insert((0, 0), Color::Red);
+/// pixels.insert((0, 1), Color::Transparent);
+/// pixels.insert((0, 2), Color::Rgb(0, 0, 255));
+/// assert_eq!(pixels.len(), 2);
+/// assert!(matches!(pixels[&(5, 5)], Color::Transparent));
+/// ```
+///
+/// On a struct, give the *common* value as an expression with `#[common(...)]` on the struct
+/// itself; values are compared with the *common* value using [PartialEq]:
+///
+/// ```
+/// use total_maps::{Commonality, TotalBTreeMap};
+///
+/// #[derive(Commonality, Debug, PartialEq)]
+/// #[common(Limit { max: 100 })]
+/// struct Limit {
+///     max: u32,
+/// }
+///
+/// let mut limits = TotalBTreeMap::<&str, Limit, LimitCommonality>::new();
+/// limits.insert("guest", Limit { max: 10 });
+/// limits.insert("user", Limit { max: 100 });
+/// assert_eq!(limits.len(), 1);
+/// assert_eq!(limits["admin"], Limit { max: 100 });
+/// ```
+///
+/// Generic types are not supported.
+#[proc_macro_derive(Commonality, attributes(common))]
+pub fn derive_commonality(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+fn expand(input: DeriveInput) -> Result<TokenStream2, Error> {
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(&input.generics, "generic types are not supported"));
+    }
+    let ty = &input.ident;
+    let (common, is_common, cheap) = match &input.data {
+        Data::Enum(data) => {
+            let mut common = data.variants.iter().filter(|variant| is_marked(&variant.attrs));
+            let variant = common.next().ok_or_else(|| {
+                Error::new(Span::call_site(), "one variant must be marked with #[common]")
+            })?;
+            if let Some(extra) = common.next() {
+                return Err(Error::new_spanned(extra, "only one variant can be marked #[common]"));
+            }
+            if !matches!(variant.fields, Fields::Unit) {
+                return Err(Error::new_spanned(variant, "the #[common] variant must be a unit"));
+            }
+            let variant = &variant.ident;
+            (quote!(#ty::#variant), quote!(matches!(value, #ty::#variant)), true)
+        }
+        Data::Struct(_) => {
+            let attr = input.attrs.iter().find(|attr| attr.path().is_ident("common"));
+            let attr = attr.ok_or_else(|| {
+                Error::new(Span::call_site(), "the common value must be given with #[common(...)]")
+            })?;
+            let common = attr.parse_args::<Expr>()?;
+            (quote!(#common), quote!(*value == #common), false)
+        }
+        Data::Union(_) => {
+            return Err(Error::new(Span::call_site(), "unions are not supported"));
+        }
+    };
+
+    let vis = &input.vis;
+    let marker = format_ident!("{}Commonality", ty);
+    let doc = format!("The [commonality](::total_maps::Commonality) of [`{ty}`].");
+    Ok(quote! {
+        #[doc = #doc]
+        #vis struct #marker(());
+
+        impl ::total_maps::Commonality<#ty> for #marker {
+            const CHEAP: bool = #cheap;
+
+            fn common() -> #ty {
+                #common
+            }
+            fn is_common(value: &#ty) -> bool {
+                #is_common
+            }
+        }
+    })
+}
+
+fn is_marked(attrs: &[Attribute]) -> bool {
+    attrs.iter().any(|attr| attr.path().is_ident("common"))
+}